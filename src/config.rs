@@ -0,0 +1,80 @@
+use anyhow::{Context, Result};
+use pgm::{Rules, Signal, TileType};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// The simulation settings read from a TOML file,
+/// named like the command line flags they provide defaults for.
+///
+/// Missing keys keep the built-in defaults.
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    pub width: Option<usize>,
+    pub height: Option<usize>,
+    pub seed: Option<u64>,
+    /// In milliseconds.
+    pub tick_interval: Option<u64>,
+    pub random_tick_rate: Option<usize>,
+    pub flag_rate: Option<f64>,
+    pub backlog_length: Option<usize>,
+    pub backlog_unit: Option<u32>,
+    pub decay: Option<f64>,
+    pub sink_drain: Option<f64>,
+    pub signal_ceiling: Option<u16>,
+    pub diagonal: Option<bool>,
+    pub wrap: Option<bool>,
+    pub deterministic: Option<bool>,
+    pub floor: Option<bool>,
+    pub colormap: Option<String>,
+    pub start_pattern: Option<String>,
+    pub mono: Option<bool>,
+    pub smooth: Option<bool>,
+    pub flip_y: Option<bool>,
+    pub label_stride: Option<usize>,
+    pub x_axis_stride: Option<usize>,
+    pub y_axis_stride: Option<usize>,
+    /// Overrides of the rules of tile types, keyed by lowercase type name.
+    pub rules: Option<BTreeMap<String, TileRulesConfig>>,
+}
+
+/// The fields of [`pgm::TileRules`] to override, keeping the defaults of missing ones.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TileRulesConfig {
+    pub accepts: Option<bool>,
+    pub emits: Option<bool>,
+    pub absorbs: Option<bool>,
+    pub weight: Option<u16>,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text =
+            fs::read_to_string(path).with_context(|| format!("cannot read {}", path.display()))?;
+        toml::from_str(&text).with_context(|| format!("cannot parse {}", path.display()))
+    }
+
+    /// The default rules with the overrides of the `rules` table applied,
+    /// or `None` if there is no such table.
+    pub fn rules(&self) -> Result<Option<Rules>> {
+        let Some(overrides) = &self.rules else {
+            return Ok(None);
+        };
+        let mut rules = Rules::default();
+        for (name, config) in overrides {
+            let ty = TileType::ALL
+                .into_iter()
+                .find(|ty| format!("{ty:?}").to_lowercase() == *name)
+                .with_context(|| format!("unknown tile type {name:?} in rules"))?;
+            let tile = &mut rules[ty];
+            tile.accepts = config.accepts.unwrap_or(tile.accepts);
+            tile.emits = config.emits.unwrap_or(tile.emits);
+            tile.absorbs = config.absorbs.unwrap_or(tile.absorbs);
+            tile.weight = config.weight.map_or(tile.weight, Signal);
+        }
+        Ok(Some(rules))
+    }
+}
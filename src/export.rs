@@ -0,0 +1,34 @@
+use std::io::{self, Write};
+
+use crate::{Signal, World};
+
+impl World {
+    /// Writes the `signal_sum` field as a binary (P5) Portable GrayMap, top row first,
+    /// normalized against [`World::max_signal_sum`] like the terminal UI does.
+    pub fn export_pgm(&self, w: impl Write) -> io::Result<()> {
+        self.export_pgm_normalized(w, self.max_signal_sum())
+    }
+
+    /// Like [`World::export_pgm`], but normalized against `max_signal_sum`,
+    /// such that consecutive frames share the same brightness scale.
+    /// Signals above `max_signal_sum` are clipped to white.
+    pub fn export_pgm_normalized(
+        &self,
+        mut w: impl Write,
+        max_signal_sum: Signal,
+    ) -> io::Result<()> {
+        let dim = self.dim();
+        write!(w, "P5\n{} {}\n255\n", dim.width, dim.height)?;
+
+        let mut row = Vec::with_capacity(dim.width);
+        for y in (0..dim.height).rev() {
+            row.clear();
+            row.extend((0..dim.width).map(|x| {
+                let ratio = self[(x, y)].signal_sum().ratio(max_signal_sum);
+                (ratio.min(1.0) * 255.0) as u8
+            }));
+            w.write_all(&row)?;
+        }
+        Ok(())
+    }
+}
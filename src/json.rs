@@ -0,0 +1,135 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::io::{self, Read, Write};
+
+use crate::sim::SIDES;
+use crate::{TileType, World};
+
+#[derive(Serialize, Deserialize)]
+struct JsonWorld {
+    width: usize,
+    height: usize,
+    tiles: Vec<JsonTile>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonTile {
+    x: usize,
+    y: usize,
+    /// The name of the [`TileType`] variant.
+    #[serde(rename = "type")]
+    ty: String,
+    signal_sum: u16,
+    /// The index in [`SIDES`] a diode faces, omitted for other tiles.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    facing: Option<usize>,
+}
+
+impl World {
+    /// Writes the size and every tile as JSON, top row first,
+    /// with the type, position and current `signal_sum` of each tile.
+    pub fn export_json(&self, w: impl Write) -> io::Result<()> {
+        let dim = self.dim();
+        let tiles = (0..dim.height)
+            .rev()
+            .flat_map(|y| (0..dim.width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let tile = &self[(x, y)];
+                JsonTile {
+                    x,
+                    y,
+                    ty: format!("{:?}", tile.ty),
+                    signal_sum: tile.signal_sum().0,
+                    facing: (tile.ty == TileType::Diode).then_some(tile.facing),
+                }
+            })
+            .collect();
+        let world = JsonWorld {
+            width: dim.width,
+            height: dim.height,
+            tiles,
+        };
+        serde_json::to_writer(w, &world).map_err(io::Error::other)
+    }
+
+    /// Reads a layout written by [`World::export_json`].
+    /// Tiles missing from the list are air,
+    /// and signals start out like in [`World::load`] since `signal_sum` is not restored.
+    pub fn load_json(r: impl Read, seed: u64) -> Result<Self> {
+        let json: JsonWorld = serde_json::from_reader(r)?;
+        let (width, height) = (json.width, json.height);
+        if width == 0 || height == 0 || width.checked_mul(height).is_none() {
+            bail!("invalid world size {width}x{height}");
+        }
+
+        let mut world = Self::new(width, height, seed);
+        for (i, tile) in json.tiles.into_iter().enumerate() {
+            if tile.x >= width || tile.y >= height {
+                bail!("tile {i} at ({}, {}) is outside the world", tile.x, tile.y);
+            }
+            let ty = TileType::ALL
+                .into_iter()
+                .find(|ty| format!("{ty:?}") == tile.ty)
+                .with_context(|| format!("unknown type {:?} of tile {i}", tile.ty))?;
+            let facing = tile.facing.unwrap_or(0);
+            if facing >= SIDES.len() {
+                bail!("invalid facing {facing} of tile {i}");
+            }
+
+            let loaded = &mut world[(tile.x, tile.y)];
+            loaded.ty = ty;
+            loaded.facing = facing;
+        }
+
+        world.reseed_flags();
+        Ok(world)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Tick;
+
+    #[test]
+    fn export_and_load_round_trip() {
+        let mut world = World::new(TileType::ALL.len(), 3, 0);
+        for (x, ty) in TileType::ALL.into_iter().enumerate() {
+            world.set_type((x, 1), ty);
+            world.set_type((x, 0), TileType::Bedrock);
+        }
+        for (x, facing) in (0..SIDES.len()).enumerate() {
+            world.set_type((x, 2), TileType::Diode);
+            world[(x, 2)].facing = facing;
+        }
+        world.tick_n(Tick(0), 20);
+
+        let mut exported = Vec::new();
+        world.export_json(&mut exported).unwrap();
+        let json: JsonWorld = serde_json::from_slice(&exported).unwrap();
+        for tile in &json.tiles {
+            assert_eq!(tile.signal_sum, world[(tile.x, tile.y)].signal_sum().0);
+        }
+
+        let loaded = World::load_json(&exported[..], 0).unwrap();
+        assert_eq!(
+            (loaded.dim().width, loaded.dim().height),
+            (world.dim().width, world.dim().height)
+        );
+        for (tile, loaded) in world.tiles().iter().zip(loaded.tiles()) {
+            assert_eq!(loaded.ty, tile.ty);
+            assert_eq!(loaded.rendered(), tile.rendered());
+            assert_eq!(loaded.signal_sum().0, 0);
+        }
+    }
+
+    #[test]
+    fn load_rejects_malformed_json() {
+        let load = |json: &str| World::load_json(json.as_bytes(), 0);
+        assert!(load(r#"{"width": 0, "height": 1, "tiles": []}"#).is_err());
+        assert!(load(r#"{"width": 1, "height": 1, "tiles": [{"x": 1, "y": 0, "type": "Brick", "signal_sum": 0}]}"#).is_err());
+        assert!(load(r#"{"width": 1, "height": 1, "tiles": [{"x": 0, "y": 0, "type": "Lava", "signal_sum": 0}]}"#).is_err());
+        assert!(load(r#"{"width": 1, "height": 1, "tiles": [{"x": 0, "y": 0, "type": "Diode", "signal_sum": 0, "facing": 4}]}"#).is_err());
+        assert!(load(r#"{"width": 1, "height": 1, "tiles": []}"#).is_ok());
+    }
+}
@@ -0,0 +1,147 @@
+use anyhow::{bail, Context, Result};
+use std::io::{self, BufRead, Write};
+
+use crate::{Tile, World};
+
+impl World {
+    /// Writes [`World::comments`] as lines starting with `#`,
+    /// then the tile layout as a `width height` header
+    /// followed by one row of rendered tiles per line, top row first.
+    pub fn save(&self, mut w: impl Write) -> io::Result<()> {
+        for comment in &self.comments {
+            writeln!(w, "# {comment}")?;
+        }
+        let dim = self.dim();
+        writeln!(w, "{} {}", dim.width, dim.height)?;
+        for y in (0..dim.height).rev() {
+            let row: String = (0..dim.width).map(|x| self[(x, y)].rendered()).collect();
+            writeln!(w, "{row}")?;
+        }
+        Ok(())
+    }
+
+    /// Reads a layout written by [`World::save`].
+    /// All signals of the loaded world start at zero with every emitter flagged,
+    /// and sources start at [`DEFAULT_SOURCE_STRENGTH`](crate::sim::DEFAULT_SOURCE_STRENGTH)
+    /// on channel 0 and conductors at [`DEFAULT_RESISTANCE`](crate::sim::DEFAULT_RESISTANCE).
+    /// Emitters have unlimited fuel.
+    ///
+    /// Comments are only recognized before the header,
+    /// since rows of conductors also start with `#`.
+    pub fn load(r: impl BufRead, seed: u64) -> Result<Self> {
+        let mut lines = r.lines();
+
+        let mut comments = Vec::new();
+        let header = loop {
+            let line = lines.next().context("missing header")??;
+            match line.strip_prefix('#') {
+                Some(comment) => {
+                    comments.push(comment.strip_prefix(' ').unwrap_or(comment).to_string())
+                }
+                None => break line,
+            }
+        };
+        let (width, height) = header
+            .split_once(' ')
+            .context("header should be \"<width> <height>\"")?;
+        let width: usize = width.parse().context("invalid width in header")?;
+        let height: usize = height.parse().context("invalid height in header")?;
+        if width == 0 || height == 0 || width.checked_mul(height).is_none() {
+            bail!("invalid world size {width}x{height}");
+        }
+
+        let mut world = Self::new(width, height, seed);
+        for y in (0..height).rev() {
+            let line_no = comments.len() + height - y + 1;
+            let row = lines
+                .next()
+                .with_context(|| format!("expected {height} rows, got {}", height - y - 1))??;
+
+            let tiles = row.chars().count();
+            if tiles != width {
+                bail!("line {line_no} has {tiles} tiles, expected {width}");
+            }
+            world.load_row(y, &row, line_no)?;
+        }
+
+        world.comments = comments;
+        world.reseed_flags();
+        Ok(world)
+    }
+
+    /// Reads a layout of rendered tiles without the header of [`World::save`],
+    /// sized to fit the longest line and padding shorter lines with air.
+    /// Signals start out like in [`World::load`].
+    pub fn load_ascii(r: impl BufRead, seed: u64) -> Result<Self> {
+        let rows = r.lines().collect::<io::Result<Vec<_>>>()?;
+        let width = rows
+            .iter()
+            .map(|row| row.chars().count())
+            .max()
+            .unwrap_or(0);
+        let height = rows.len();
+        if width == 0 {
+            bail!("the layout is empty");
+        }
+
+        let mut world = Self::new(width, height, seed);
+        for (i, row) in rows.iter().enumerate() {
+            world.load_row(height - 1 - i, row, i + 1)?;
+        }
+
+        world.reseed_flags();
+        Ok(world)
+    }
+
+    fn load_row(&mut self, y: usize, row: &str, line_no: usize) -> Result<()> {
+        for (x, ch) in row.chars().enumerate() {
+            self[(x, y)] = Tile::from_rendered(ch, self.backlog_length()).with_context(|| {
+                format!("unknown tile {ch:?} at line {line_no}, column {}", x + 1)
+            })?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TileType;
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let mut world = World::new(5, 3, 0);
+        world.comments = vec!["round trip".to_string()];
+        for (x, ty) in TileType::ALL.into_iter().skip(1).take(5).enumerate() {
+            world.set_type((x, 2), ty);
+        }
+        for x in 0..5 {
+            world.set_type((x, 0), TileType::Bedrock);
+        }
+        world.set_type((1, 1), TileType::Diode);
+        world[(1, 1)].facing = 3;
+        world.set_type((3, 1), TileType::Conductor);
+
+        let mut saved = Vec::new();
+        world.save(&mut saved).unwrap();
+        let loaded = World::load(&saved[..], 0).unwrap();
+
+        assert_eq!(loaded.comments, world.comments);
+        assert_eq!(
+            (loaded.dim().width, loaded.dim().height),
+            (world.dim().width, world.dim().height)
+        );
+        for (tile, loaded) in world.tiles().iter().zip(loaded.tiles()) {
+            assert_eq!(loaded.ty, tile.ty);
+            assert_eq!(loaded.rendered(), tile.rendered());
+            assert_eq!(loaded.signal_sum().0, 0);
+        }
+    }
+
+    #[test]
+    fn load_rejects_malformed_layouts() {
+        assert!(World::load("2 1\no?\n".as_bytes(), 0).is_err());
+        assert!(World::load("2 2\noo\no\n".as_bytes(), 0).is_err());
+        assert!(World::load("2 2\noo\n".as_bytes(), 0).is_err());
+    }
+}
@@ -0,0 +1,9 @@
+//! The propagative gravitation model,
+//! simulated independently of the terminal UI.
+
+mod export;
+mod json;
+mod layout;
+pub mod sim;
+
+pub use sim::{Dim, Probe, Rules, Signal, Stats, Tick, Tile, TileRules, TileType, World};
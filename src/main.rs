@@ -1,366 +1,2796 @@
-use anyhow::Result;
-use arrayvec::ArrayVec;
-use crossterm::event::{Event, KeyCode, KeyEvent};
-use crossterm::style::{Color, Stylize};
-use crossterm::{cursor, style, terminal, QueueableCommand};
-use crossterm::{
-    event,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
-    ExecutableCommand,
+mod config;
+mod replay;
+mod screen;
+#[cfg(feature = "snapshot")]
+mod snapshot;
+
+use anyhow::{bail, Context, Result};
+use config::Config;
+use crossterm::event::{
+    self, Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+};
+use crossterm::style::Color;
+use crossterm::terminal;
+use pgm::sim::{
+    CHANNELS, DEFAULT_FLAG_RATE, DEFAULT_RANDOM_TICK_PERCENTAGE, DEFAULT_SIGNAL_BACKLOG_LENGTH,
+    DEFAULT_SIGNAL_BACKLOG_UNIT, DEFAULT_SINK_DRAIN, MAX_SIGNAL_BACKLOG_LENGTH,
 };
-use rand::Rng;
+use pgm::{Dim, Rules, Signal, Tick, Tile, TileType, World};
+use replay::{InputRecorder, Replay};
+use screen::{Screen, TerminalGuard};
+#[cfg(feature = "snapshot")]
+use snapshot::Snapshot;
+use std::collections::VecDeque;
 use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::time::{Duration, Instant};
-use std::{io, iter, mem, ops};
+use std::{env, error, fmt, fs, io, mem, ops};
 
 const PADDING_TOP: usize = 5;
 const PADDING_LEFT: usize = 8;
 const PADDING_RIGHT: usize = 5;
+/// Rows below the grid, holding the x axis labels and the status lines.
+const PADDING_BOTTOM: usize = 6;
+const LEGEND_WIDTH: usize = 8;
+/// Columns right of the legend, holding the statistics panel.
+const STATS_WIDTH: usize = 18;
+/// The smallest viewport worth drawing; smaller terminals only see a message.
+const MIN_VIEWPORT_WIDTH: usize = 10;
+const MIN_VIEWPORT_HEIGHT: usize = 8;
+/// Roughly how many labels each axis gets unless its stride is set explicitly.
+const AXIS_LABELS: usize = 8;
+/// The number of levels each channel is quantized to by [`mix_channels`], such that
+/// tiles colored by their channels are only redrawn when their signal changes visibly.
+const COLOR_LEVELS: usize = 64;
+/// The number of colors sampled from the colormap into [`View::palette`].
+/// [`shade`] maps each channel into 128..=255, so neighboring levels often share a color,
+/// and tiles are only redrawn when their color actually changes.
+const PALETTE_LEVELS: usize = 256;
+/// Shading characters from no signal to the maximum signal, for `--mono`.
+const MONO_RAMP: [char; 10] = [' ', '.', ':', '-', '=', '+', '*', '#', '%', '@'];
+/// Characters from the weakest to the maximum signal, for the heightmap.
+const HEIGHT_RAMP: [char; 5] = ['.', 'o', 'O', '0', '@'];
+/// Drawn right of locked tiles, except in `--mono` where that column holds the shade.
+const LOCK_MARKER: char = '\'';
+/// The bars of probe sparklines, from no signal to the highest probed signal.
+const SPARK_RAMP: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
 
-const SIGNAL_BACKLOG_LENGTH: usize = 4;
-const SIGNAL_BACKLOG_UNIT: Tick = Tick(8);
-const FLAG_UPDATED_RATE: f64 = 0.8;
-const RANDOM_TICK_PERCENTAGE: usize = 20;
 const WORLD_WIDTH: usize = 80;
 const WORLD_HEIGHT: usize = 40;
-const SIDES: [(isize, isize); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+const HISTORY_LENGTH: usize = 100;
+const SAVE_PATH: &str = "world.pgm";
+/// Screenshots ending in `.txt` are written without colors.
+const SCREENSHOT_PATH: &str = "screen.ans";
+const EXPORT_PATH: &str = "signal.pgm";
+const JSON_EXPORT_PATH: &str = "world.json";
+/// The config file read on startup if it exists and `--config` names no other.
+const CONFIG_PATH: &str = "pgm.toml";
+#[cfg(feature = "snapshot")]
+const SNAPSHOT_PATH: &str = "snapshot.bin";
+/// The blank columns between the columns of the help overlay.
+const HELP_COLUMN_GAP: usize = 4;
+/// Every key binding with a description, as listed by the help overlay.
+/// New keys handled in `main` must be registered here.
+const KEYBINDINGS: &[(&str, &str)] = &[
+    ("q", "quit"),
+    ("h j k l", "move the cursor left, down, up, right"),
+    ("U", "toggle drawing row 0 at the top instead of the bottom"),
+    ("<count>", "repeat the next cursor motion, e.g. 10l"),
+    ("Home End", "jump the cursor to the first or last column"),
+    ("g g  G", "jump the cursor to the top or bottom row"),
+    (
+        "a r b ~ d s S e X",
+        "select air, bedrock, brick, water, diode, source, sink, conductor, wall as brush",
+    ),
+    (
+        "Tab Shift-Tab",
+        "cycle the brush forwards or backwards through every tile type",
+    ),
+    ("Enter", "stamp the brush at the cursor"),
+    (
+        "Z",
+        "put the pen down or lift it, stamping the brush along every h j k l motion",
+    ),
+    ("x", "erase the tile under the cursor"),
+    ("< >", "shrink or grow the brush and eraser"),
+    (
+        "v",
+        "start a rectangle selection, or fill it with the brush",
+    ),
+    ("L", "start a line selection, or draw it with the brush"),
+    (
+        "E",
+        "swap brick with air and sources with sinks across the selection",
+    ),
+    ("Esc", "cancel the selection"),
+    ("f", "flood fill the region under the cursor with the brush"),
+    ("u", "undo"),
+    ("Ctrl-r", "redo"),
+    ("o", "rotate the diode under the cursor clockwise"),
+    (
+        "[ ]",
+        "weaken or strengthen the source or conductor under the cursor",
+    ),
+    (
+        "( )",
+        "lower or raise the fuel of the emitter under the cursor, unlimited above the maximum",
+    ),
+    (
+        "arrows",
+        "grow or shrink the world, clearing the undo history",
+    ),
+    ("D", "toggle diagonal propagation"),
+    ("m", "cycle the colormap"),
+    (
+        "N",
+        "cycle normalizing colors against the frame maximum, a running maximum or the ceiling",
+    ),
+    ("M", "toggle mixing the channels as red, green and blue"),
+    (
+        "I",
+        "toggle interpolating the signal between ticks, redrawing more often",
+    ),
+    (
+        "H",
+        "toggle drawing the signal as a heightmap, coloring by tile type",
+    ),
+    (
+        "V",
+        "toggle labeling tiles with their signal in tenths of the maximum",
+    ),
+    (
+        "F",
+        "toggle highlighting the tiles flagged for the next tick",
+    ),
+    (
+        "O",
+        "toggle coloring tiles by the ticks since they were last flagged instead of signal",
+    ),
+    ("n", "move the source under the cursor to the next channel"),
+    ("i", "inspect the signal backlog under the cursor"),
+    (
+        "B",
+        "inject a one-time burst of signal into the tile under the cursor",
+    ),
+    (", .", "lower or raise the signal of bursts"),
+    (
+        "<count>z",
+        "trace where signal from the cursor reaches within count hops, or stop tracing",
+    ),
+    (
+        "K",
+        "lock or unlock the tile under the cursor against edits and clearing",
+    ),
+    ("P", "toggle plotting the signal under the cursor over time"),
+    (
+        "A",
+        "fill the whole world with the brush, confirming if not empty",
+    ),
+    (
+        "c c",
+        "clear the world, keeping the bedrock floor unless --no-floor",
+    ),
+    ("C", "clear the signals, keeping the tiles"),
+    ("t", "run a single tick"),
+    ("T", "prompt for a number of ticks to run at once"),
+    ("space", "pause or resume"),
+    (
+        "R",
+        "pause or resume random ticks, letting existing signal propagate",
+    ),
+    ("+ -", "speed up or slow down ticking"),
+    ("{ }", "lower or raise the rate of flagging neighbors"),
+    ("w", "save the layout"),
+    #[cfg(feature = "snapshot")]
+    (
+        "W",
+        "save a snapshot of the whole simulation to resume from",
+    ),
+    ("p", "export the signal as PGM"),
+    ("J", "export the tiles and their signal as JSON"),
+    ("Y", "save the screen as text, colored unless saved as .txt"),
+    (
+        "left click",
+        "move the cursor and paint the brush, drag to keep painting",
+    ),
+    ("right click", "erase to air, drag to keep erasing"),
+    ("?", "show this help, paged with space if it does not fit"),
+];
+/// The factor the running maximum decays by every frame when normalizing against it.
+const RUNNING_MAX_DECAY: f64 = 0.99;
+/// The change of source strength per key press.
+const STRENGTH_STEP: u16 = 10;
+/// The change of conductor resistance per key press, in percent.
+const RESISTANCE_STEP: u8 = 5;
+/// The change of emitter fuel per key press, in random ticks.
+const FUEL_STEP: u32 = 10;
+/// The highest limited fuel, raising beyond which makes the fuel unlimited.
+const MAX_FUEL: u32 = 1000;
+/// The pairs of tile types swapped across the selection by `E`, listed in [`KEYBINDINGS`].
+const SWAPS: [(TileType, TileType); 2] = [
+    (TileType::Brick, TileType::Air),
+    (TileType::Source, TileType::Sink),
+];
+/// The signal of bursts injected with `B` until changed.
+const DEFAULT_BURST: Signal = Signal(1000);
+/// The change of the burst signal per key press.
+const BURST_STEP: u16 = 100;
+const MAX_BRUSH_RADIUS: usize = 16;
+/// The hop limit of `z` without a count.
+const DEFAULT_TRACE_HOPS: usize = 10;
+/// The ticks `--bench` runs without a count or `--max-ticks`.
+const DEFAULT_BENCH_TICKS: u32 = 1000;
 const TICK_FREQ: Duration = Duration::from_millis(1000);
+const MIN_TICK_FREQ: Duration = Duration::from_millis(16);
+const MAX_TICK_FREQ: Duration = Duration::from_secs(10);
+/// The number of ticks after being flagged at which tiles colored by activity turn cold.
+const ACTIVITY_HORIZON: u32 = 32;
+/// The time between frames while interpolating between ticks.
+const SMOOTH_FRAME_INTERVAL: Duration = Duration::from_millis(33);
+/// How often the `--log` file is flushed, so that it can be followed with `tail -f`.
+const LOG_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
 
-#[derive(Debug, Clone, Copy)]
-struct Tick(u32);
-#[derive(Debug, Clone, Copy)]
-struct Signal(u16);
+/// The window of the world visible on the terminal, in world coordinates.
+#[derive(Default)]
+struct Viewport {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+    /// The terminal size needed to draw the viewport at its minimum size,
+    /// if the terminal is smaller than that.
+    too_small: Option<(usize, usize)>,
+}
 
-#[derive(Clone)]
-struct Tile {
-    ty: TileType,
-    signals: [Signal; SIGNAL_BACKLOG_LENGTH],
-    signal_sum: Signal,
+impl Viewport {
+    /// The terminal size needed to draw a viewport of `width` by `height` tiles.
+    fn term_size_for(width: usize, height: usize) -> (usize, usize) {
+        (
+            PADDING_LEFT + width * 2 + PADDING_RIGHT + LEGEND_WIDTH + STATS_WIDTH,
+            PADDING_TOP + height + PADDING_BOTTOM,
+        )
+    }
 
-    next_signal: Signal,
-}
+    /// Sizes the viewport to fit a terminal of `cols` by `rows`,
+    /// scrolling just enough to keep `cursor` visible.
+    fn fit(&mut self, dim: &Dim, (cols, rows): (u16, u16), cursor: (usize, usize)) {
+        let (margin_cols, margin_rows) = Self::term_size_for(0, 0);
+        let (min_cols, min_rows) = Self::term_size_for(
+            MIN_VIEWPORT_WIDTH.min(dim.width),
+            MIN_VIEWPORT_HEIGHT.min(dim.height),
+        );
+        self.too_small = (usize::from(cols) < min_cols || usize::from(rows) < min_rows)
+            .then_some((min_cols, min_rows));
 
-impl Default for Tile {
-    fn default() -> Self {
-        Self {
-            ty: TileType::Air,
-            signals: [Signal(0); SIGNAL_BACKLOG_LENGTH],
-            signal_sum: Signal(0),
-            next_signal: Signal(0),
+        let cols = usize::from(cols).saturating_sub(margin_cols);
+        let rows = usize::from(rows).saturating_sub(margin_rows);
+        self.width = (cols / 2).clamp(1, dim.width);
+        self.height = rows.clamp(1, dim.height);
+
+        fn scroll(origin: usize, size: usize, limit: usize, cursor: usize) -> usize {
+            let origin = origin.min(cursor).max((cursor + 1).saturating_sub(size));
+            origin.min(limit - size)
         }
+        self.x = scroll(self.x, self.width, dim.width, cursor.0);
+        self.y = scroll(self.y, self.height, dim.height, cursor.1);
+    }
+
+    fn xs(&self) -> ops::Range<usize> {
+        self.x..self.x + self.width
+    }
+    fn ys(&self) -> ops::Range<usize> {
+        self.y..self.y + self.height
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-enum TileType {
-    Air,
-    Bedrock,
-    Brick,
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum Colormap {
+    #[default]
+    Viridis,
+    Plasma,
+    Magma,
+    Inferno,
+    Turbo,
 }
 
-impl TileType {
-    fn rendered(self) -> char {
+impl Colormap {
+    const ALL: [Self; 5] = [
+        Self::Viridis,
+        Self::Plasma,
+        Self::Magma,
+        Self::Inferno,
+        Self::Turbo,
+    ];
+
+    fn name(self) -> &'static str {
         match self {
-            Self::Air => ' ',
-            Self::Bedrock => '=',
-            Self::Brick => 'o',
+            Self::Viridis => "viridis",
+            Self::Plasma => "plasma",
+            Self::Magma => "magma",
+            Self::Inferno => "inferno",
+            Self::Turbo => "turbo",
         }
     }
 
-    fn weight(self) -> Signal {
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|colormap| colormap.name() == name)
+    }
+
+    fn gradient(self) -> colorgrad::Gradient {
         match self {
-            Self::Air => Signal(0),
-            Self::Bedrock => Signal(0),
-            Self::Brick => Signal(100),
+            Self::Viridis => colorgrad::viridis(),
+            Self::Plasma => colorgrad::plasma(),
+            Self::Magma => colorgrad::magma(),
+            Self::Inferno => colorgrad::inferno(),
+            Self::Turbo => colorgrad::turbo(),
         }
     }
 
-    fn accepts(self) -> bool {
-        matches!(self, Self::Bedrock | Self::Brick)
-    }
-    fn emits(self) -> bool {
-        matches!(self, Self::Brick)
-    }
-    fn absorbs(self) -> bool {
-        matches!(self, Self::Bedrock)
+    /// The colormap after this one in [`Colormap::ALL`], wrapping around.
+    fn next(self) -> Self {
+        let index = Self::ALL
+            .iter()
+            .position(|&colormap| colormap == self)
+            .unwrap();
+        Self::ALL[(index + 1) % Self::ALL.len()]
     }
 }
 
-struct Dim {
-    width: usize,
-    height: usize,
+/// A built-in layout placed on new worlds to show propagation right away.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum StartPattern {
+    /// A single brick in the center.
+    Brick,
+    /// A diagonal line of bricks through the center.
+    Diagonal,
+    /// A box of bedrock around a source in the center.
+    Box,
 }
 
-impl Dim {
-    fn xy_offset(&self, x: usize, y: usize) -> usize {
-        assert!(x < self.width);
-        assert!(y < self.height);
-        x + y * self.width
+impl StartPattern {
+    const ALL: [Self; 3] = [Self::Brick, Self::Diagonal, Self::Box];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Brick => "brick",
+            Self::Diagonal => "diagonal",
+            Self::Box => "box",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|pattern| pattern.name() == name)
     }
 
-    fn offset_xy(&self, offset: usize) -> (usize, usize) {
-        assert!(offset < self.width * self.height);
-        let x = offset % self.width;
-        let y = offset / self.width;
-        (x, y)
+    /// Places the pattern around the center of `world`, flagging its emitters.
+    fn lay(self, world: &mut World) {
+        let Dim { width, height } = *world.dim();
+        let center = (width / 2, height / 2);
+        // half the side of the square the pattern spans
+        let radius = width.min(height) / 4;
+        match self {
+            Self::Brick => world.set_type(center, TileType::Brick),
+            Self::Diagonal => {
+                for i in 0..=radius * 2 {
+                    let xy = (center.0 + i - radius, center.1 + i - radius);
+                    world.set_type(xy, TileType::Brick);
+                }
+            }
+            Self::Box => {
+                for x in center.0 - radius..=center.0 + radius {
+                    for y in center.1 - radius..=center.1 + radius {
+                        let edge = x.abs_diff(center.0).max(y.abs_diff(center.1)) == radius;
+                        if edge && radius > 0 {
+                            world.set_type((x, y), TileType::Bedrock);
+                        }
+                    }
+                }
+                world.set_type(center, TileType::Source);
+            }
+        }
     }
 }
 
-struct World {
-    dim: Dim,
-    tiles: Vec<Tile>,
-    flagged_tiles: Vec<usize>,
-    next_flagged_tiles: Vec<usize>,
+/// A popup drawn over the grid until any key is pressed.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Overlay {
+    /// The signal backlog of the cursor tile.
+    Inspect,
+    /// The list of [`KEYBINDINGS`], showing the `page`th screenful if they do not all fit.
+    Help { page: usize },
+}
+
+/// The part of the terminal UI that follows the cursor.
+#[derive(Default)]
+struct View {
     cursor: (usize, usize),
+    viewport: Viewport,
+    colormap: Colormap,
+    /// [`PALETTE_LEVELS`] colors sampled from `colormap` once rather than every frame,
+    /// kept in sync by [`View::set_colormap`].
+    palette: Vec<Color>,
+    /// Shade tiles with [`MONO_RAMP`] instead of colors.
+    mono: bool,
+    /// The slowly decaying maximum to normalize colors against,
+    /// or `None` to normalize against the maximum of each frame.
+    running_max: Option<f64>,
+    /// Normalize colors against [`World::signal_ceiling`], taking precedence over `running_max`.
+    against_ceiling: bool,
+    /// Color tiles by mixing their channels as red, green and blue instead of the colormap.
+    channels: bool,
+    /// Shade tiles by how recently they were flagged instead of by their signal.
+    activity: bool,
+    /// Blend the signal of each tile from `previous_signal_sums` towards its current one
+    /// as the next tick approaches.
+    smooth: bool,
+    /// The [`Tile::signal_sums`] of every tile before the last tick,
+    /// kept by [`View::remember_signals`] while `smooth` is set.
+    previous_signal_sums: Vec<[Signal; CHANNELS]>,
+    /// Draw the signal with [`HEIGHT_RAMP`] and the tile type as the color.
+    heightmap: bool,
+    /// Label every tile whose coordinates are multiples of the stride
+    /// with its signal as a digit right of the glyph, or `None` to not label tiles.
+    labels: Option<usize>,
+    /// Draw row 0 at the top of the grid like a text buffer instead of at the bottom.
+    flip_y: bool,
+    /// Label every this many columns, or `None` to adapt to the viewport width.
+    x_axis_stride: Option<usize>,
+    /// Label every this many rows, or `None` to adapt to the viewport height.
+    y_axis_stride: Option<usize>,
+    /// The tile and hop limit to highlight the [`World::reach`] of.
+    trace: Option<((usize, usize), usize)>,
+    /// Highlight the tiles in [`World::flagged_tiles`].
+    show_flagged: bool,
 }
 
-impl World {
-    fn new(width: usize, height: usize) -> Self {
-        Self {
-            dim: Dim { width, height },
-            tiles: iter::repeat(Tile::default()).take(width * height).collect(),
-            flagged_tiles: Vec::new(),
-            next_flagged_tiles: Vec::new(),
-            cursor: (0, 0),
+impl View {
+    fn set_colormap(&mut self, colormap: Colormap) {
+        let gradient = colormap.gradient();
+        self.colormap = colormap;
+        self.palette = (0..PALETTE_LEVELS)
+            .map(|level| shade(&gradient, level as f64 / (PALETTE_LEVELS - 1) as f64))
+            .collect();
+    }
+
+    /// The color of `ratio` in the colormap, looked up in the palette.
+    fn color(&self, ratio: f64) -> Color {
+        self.palette[(ratio * (PALETTE_LEVELS - 1) as f64).round() as usize]
+    }
+
+    fn remember_signals(&mut self, world: &World) {
+        self.previous_signal_sums.clear();
+        if self.smooth {
+            let sums = world.tiles().iter().map(|tile| tile.signal_sums());
+            self.previous_signal_sums.extend(sums);
         }
     }
 
-    fn tick(&mut self, now: Tick) {
-        self.pre_tick(now);
+    fn fit_viewport(&mut self, dim: &Dim, term_size: (u16, u16)) {
+        self.viewport.fit(dim, term_size, self.cursor);
+    }
 
-        let next_flagged_tiles = mem::replace(
-            &mut self.next_flagged_tiles,
-            Vec::with_capacity(self.flagged_tiles.len()),
-        );
-        let flagged_tiles = mem::replace(&mut self.flagged_tiles, next_flagged_tiles);
-        for flagged in flagged_tiles {
-            self.flagged_tick(flagged);
+    /// Lets the running maximum decay towards `max_signal_sum` without falling below it.
+    fn adapt_running_max(&mut self, max_signal_sum: Signal) {
+        if let Some(running_max) = &mut self.running_max {
+            *running_max = (*running_max * RUNNING_MAX_DECAY).max(f64::from(max_signal_sum.0));
         }
-        mem::swap(&mut self.flagged_tiles, &mut self.next_flagged_tiles);
-        self.next_flagged_tiles.clear();
+    }
 
-        let results = rand::seq::index::sample(
-            &mut rand::thread_rng(),
-            self.tiles.len(),
-            self.tiles.len() * RANDOM_TICK_PERCENTAGE / 100,
-        );
-        for result in results {
-            self.random_tick(result);
+    /// Moves the cursor `count` times by `(dx, dy)`, stopping at the grid edges.
+    /// A positive `dy` moves the cursor up on screen, which is towards lower y if `flip_y`.
+    fn move_cursor(&mut self, dim: &Dim, (dx, dy): (isize, isize), count: usize) {
+        let dy = if self.flip_y { -dy } else { dy };
+        let count = isize::try_from(count).unwrap_or(isize::MAX);
+        let x = self
+            .cursor
+            .0
+            .saturating_add_signed(dx.saturating_mul(count));
+        let y = self
+            .cursor
+            .1
+            .saturating_add_signed(dy.saturating_mul(count));
+        self.cursor = (x.min(dim.width - 1), y.min(dim.height - 1));
+    }
+
+    fn term_x(&self, x: usize) -> u16 {
+        (PADDING_LEFT + (x - self.viewport.x) * 2) as u16
+    }
+    /// World y grows upwards, so row 0 is drawn at the bottom of the grid unless `flip_y`.
+    fn term_y(&self, y: usize) -> u16 {
+        let dy = y - self.viewport.y;
+        if self.flip_y {
+            (PADDING_TOP + 1 + dy) as u16
+        } else {
+            (PADDING_TOP + self.viewport.height - dy) as u16
         }
     }
 
-    fn pre_tick(&mut self, now: Tick) {
-        let current_signal_offset =
-            (now.0 / SIGNAL_BACKLOG_UNIT.0) as usize % SIGNAL_BACKLOG_LENGTH;
+    /// The ratio of the maximum the legend shows in the row of `y`,
+    /// rising towards the top whichever way the grid is drawn.
+    fn legend_ratio(&self, y: usize) -> f64 {
+        let i = y - self.viewport.y;
+        let level = if self.flip_y {
+            self.viewport.height - 1 - i
+        } else {
+            i
+        };
+        level as f64 / self.viewport.height as f64
+    }
+
+    /// The glyph of a tile, with diodes pointing the way they face on screen.
+    fn glyph(&self, tile: &Tile) -> char {
+        match tile.rendered() {
+            '^' if self.flip_y => 'v',
+            'v' if self.flip_y => '^',
+            glyph => glyph,
+        }
+    }
 
-        for tile in &mut self.tiles {
-            tile.signal_sum.0 -= tile.signals[current_signal_offset].0;
-            tile.signal_sum.0 += tile.next_signal.0;
-            tile.signals[current_signal_offset] = tile.next_signal;
+    /// The grid cell drawn at the terminal position, the inverse of `term_x` and `term_y`.
+    fn grid_xy(&self, column: u16, row: u16) -> Option<(usize, usize)> {
+        if self.viewport.too_small.is_some() {
+            return None;
         }
+        let dx = usize::from(column).checked_sub(PADDING_LEFT)? / 2;
+        let dy = if self.flip_y {
+            usize::from(row).checked_sub(PADDING_TOP + 1)?
+        } else {
+            (PADDING_TOP + self.viewport.height).checked_sub(usize::from(row))?
+        };
+        (dx < self.viewport.width && dy < self.viewport.height)
+            .then(|| (self.viewport.x + dx, self.viewport.y + dy))
     }
 
-    fn flagged_tick(&mut self, tile_offset: usize) {
-        let (x, y) = self.dim.offset_xy(tile_offset);
-        let Tile { ty, signal_sum, .. } = self.tiles[tile_offset];
-        if ty.emits() {
-            let mut conns = ArrayVec::<_, 4>::new();
+    /// Draws the frame into `screen` and flushes it to the terminal,
+    /// `progress` of the way from the previous signals to the current ones if smooth.
+    #[allow(clippy::too_many_arguments)]
+    fn draw(
+        &self,
+        screen: &mut Screen,
+        term_size: (u16, u16),
+        world: &World,
+        status: &str,
+        now: Tick,
+        overlay: Option<Overlay>,
+        progress: f64,
+    ) -> Result<()> {
+        screen.begin(term_size);
 
-            for (side, (dx, dy)) in SIDES.into_iter().enumerate() {
-                if let (Some(x2), Some(y2)) = (x.checked_add_signed(dx), y.checked_add_signed(dy)) {
-                    let neighbor_ty = self[(x2, y2)].ty;
-                    if neighbor_ty.accepts() {
-                        conns.push(side);
-                    }
-                }
+        if let Some((min_cols, min_rows)) = self.viewport.too_small {
+            screen.print(0, 0, "terminal too small");
+            screen.print(
+                0,
+                1,
+                format_args!(
+                    "need {min_cols}x{min_rows}, have {}x{}",
+                    term_size.0, term_size.1
+                ),
+            );
+            screen.flush((0, 0))?;
+            return Ok(());
+        }
+
+        if let Some(Overlay::Help { page }) = overlay {
+            let key_width = KEYBINDINGS.iter().map(|(key, _)| key.len()).max().unwrap();
+            let (rows, columns) = help_page_size(term_size);
+            let pages = help_pages(term_size);
+            let entries = KEYBINDINGS
+                .chunks(rows * columns)
+                .nth(page)
+                .unwrap_or_default();
+            for (i, (key, description)) in entries.iter().enumerate() {
+                screen.print(
+                    (2 + i / rows * (help_entry_width() + HELP_COLUMN_GAP)) as u16,
+                    (i % rows + 1) as u16,
+                    format_args!("{key:>key_width$}  {description}"),
+                );
+            }
+            let footer = if page + 1 < pages {
+                format!(
+                    "page {}/{pages}, space for the next page, any other key to close",
+                    page + 1
+                )
+            } else if pages > 1 {
+                format!("page {pages}/{pages}, press any key to close")
+            } else {
+                "press any key to close".to_string()
+            };
+            screen.print(2, (entries.len().min(rows) + 2) as u16, footer);
+            screen.flush((0, 0))?;
+            return Ok(());
+        }
+
+        let y_stride = self
+            .y_axis_stride
+            .unwrap_or_else(|| axis_stride(self.viewport.height, 1));
+        for y in self.viewport.ys().filter(|y| y % y_stride == 0) {
+            screen.print(1, self.term_y(y), y);
+        }
+
+        // each tile is two columns wide, and labels need a blank column between them
+        let x_digits = (self.viewport.x + self.viewport.width - 1)
+            .to_string()
+            .len();
+        let x_stride = self
+            .x_axis_stride
+            .unwrap_or_else(|| axis_stride(self.viewport.width, (x_digits + 2) / 2));
+        let x_term_y = (self.viewport.height + PADDING_TOP + 2) as u16;
+        for x in self.viewport.xs().filter(|x| x % x_stride == 0) {
+            screen.print(self.term_x(x), x_term_y, x);
+        }
+
+        let max_signal_sum = match self.running_max {
+            _ if self.against_ceiling => world.signal_ceiling,
+            Some(running_max) => Signal(running_max as u16),
+            None => world.max_signal_sum(),
+        };
+        let color = |ratio: f64| self.color(ratio);
+        // each channel is normalized on its own so that weak channels stay visible
+        let max_signal_sums = world.max_signal_sums();
+        // a resize since the last tick leaves nothing to interpolate from
+        let previous_signal_sums = (self.smooth
+            && self.previous_signal_sums.len() == world.tiles().len())
+        .then_some(&self.previous_signal_sums);
+
+        let mut flagged = Vec::new();
+        if self.show_flagged {
+            flagged.resize(world.tiles().len(), false);
+            for &offset in world.flagged_tiles() {
+                flagged[offset] = true;
             }
+        }
 
-            self[(x, y)].next_signal.0 -= signal_sum.0;
+        // the hop count of each traced tile, with the tiles at the hop limit as the frontier
+        let mut traced = Vec::new();
+        if let Some((origin, max_hops)) = self.trace {
+            traced.resize(world.tiles().len(), None);
+            for ((x, y), hops) in world.reach(origin, max_hops) {
+                traced[world.dim().xy_offset(x, y)] = Some(hops == max_hops);
+            }
+        }
 
-            let per_side = signal_sum.0 / conns.len() as u16;
-            for side in conns {
-                let (dx, dy) = SIDES[side];
-                let x2 = x.checked_add_signed(dx).unwrap();
-                let y2 = y.checked_add_signed(dy).unwrap();
-                let neighbor = &mut self[(x2, y2)];
-                if !neighbor.ty.absorbs() {
-                    neighbor.next_signal.0 += per_side;
+        for x in self.viewport.xs() {
+            for y in self.viewport.ys() {
+                let tile = &world[(x, y)];
+                let offset = world.dim().xy_offset(x, y);
+                let bg = match (traced.get(offset), flagged.get(offset)) {
+                    (Some(Some(true)), _) => Color::DarkMagenta,
+                    (Some(Some(false)), _) => Color::DarkBlue,
+                    (_, Some(true)) => Color::DarkGrey,
+                    _ => Color::Reset,
+                };
+                let mut shown = tile.signal_sums().map(|signal| f64::from(signal.0));
+                if let Some(previous) = previous_signal_sums {
+                    for (shown, previous) in shown.iter_mut().zip(previous[offset]) {
+                        let previous = f64::from(previous.0);
+                        *shown = previous + (*shown - previous) * progress;
+                    }
                 }
+                let ratio = if self.activity {
+                    activity_ratio(tile.last_active_tick, now)
+                } else {
+                    // the previous signal may exceed the maximum of this frame
+                    shown_ratio(shown.iter().sum(), max_signal_sum)
+                };
+                let fg = if self.channels && !self.activity {
+                    let mut ratios = [0.0; CHANNELS];
+                    for (channel, ratio) in ratios.iter_mut().enumerate() {
+                        *ratio = shown_ratio(shown[channel], max_signal_sums[channel]);
+                    }
+                    mix_channels(ratios)
+                } else {
+                    color(ratio)
+                };
 
-                if rand::thread_rng().gen_bool(FLAG_UPDATED_RATE) {
-                    self.next_flagged_tiles.push(self.dim.xy_offset(x2, y2));
+                // the cursor inverts the colors of its tile to stand out
+                let is_cursor = (x, y) == self.cursor;
+                let label = self
+                    .labels
+                    .filter(|stride| x % stride == 0 && y % stride == 0)
+                    .map(|_| signal_digit(ratio));
+                if self.mono {
+                    let (fg, bg) = if is_cursor {
+                        (Color::Black, Color::White)
+                    } else {
+                        (Color::Reset, bg)
+                    };
+                    // the shade goes into the column right of the glyph
+                    let shade = match label {
+                        Some(digit) => digit,
+                        None => mono_shade(ratio),
+                    };
+                    screen.print_styled(
+                        self.term_x(x),
+                        self.term_y(y),
+                        format_args!("{}{shade}", self.glyph(tile)),
+                        fg,
+                        bg,
+                    );
+                } else {
+                    let (glyph, fg) = if self.heightmap {
+                        // tiles without signal keep their glyph so that barriers stay visible
+                        let glyph = match height_char(ratio) {
+                            Some(ch) => ch,
+                            None => self.glyph(tile),
+                        };
+                        (glyph, tile_color(tile.ty))
+                    } else {
+                        (self.glyph(tile), fg)
+                    };
+                    let (fg, bg) = if is_cursor {
+                        (Color::Black, fg)
+                    } else {
+                        (fg, bg)
+                    };
+                    screen.print_styled(self.term_x(x), self.term_y(y), glyph, fg, bg);
+                    if let Some(digit) = label {
+                        screen.print_styled(self.term_x(x) + 1, self.term_y(y), digit, fg, bg);
+                    } else if tile.locked {
+                        screen.print_styled(
+                            self.term_x(x) + 1,
+                            self.term_y(y),
+                            LOCK_MARKER,
+                            Color::DarkGrey,
+                            bg,
+                        );
+                    }
                 }
             }
         }
-    }
 
-    fn random_tick(&mut self, tile_offset: usize) {
-        let tile = &mut self.tiles[tile_offset];
-        tile.next_signal.0 += tile.ty.weight().0;
+        let colormap_term_x = (PADDING_LEFT + self.viewport.width * 2 + PADDING_RIGHT) as u16;
+        if self.channels && !self.mono && !self.heightmap && !self.activity {
+            // the maximum of each channel, shown in its pure color
+            for (channel, max) in max_signal_sums.iter().enumerate() {
+                let mut ratios = [0.0; CHANNELS];
+                ratios[channel] = 1.0;
+                screen.print_styled(
+                    colormap_term_x,
+                    (PADDING_TOP + 1 + channel) as u16,
+                    format_args!("{}", max.0),
+                    Color::Black,
+                    mix_channels(ratios),
+                );
+            }
+        } else {
+            for y in self.viewport.ys() {
+                let ratio = self.legend_ratio(y);
+                // the age in ticks when coloring by activity
+                let value = if self.activity {
+                    format!("{:.0}", (1.0 - ratio) * ACTIVITY_HORIZON as f64)
+                } else {
+                    format!("{:.1}", ratio * max_signal_sum.0 as f64)
+                };
+                if self.mono {
+                    screen.print(
+                        colormap_term_x,
+                        self.term_y(y),
+                        format_args!("{} {value}", mono_shade(ratio)),
+                    );
+                } else if self.heightmap {
+                    let ch = height_char(ratio).unwrap_or(' ');
+                    screen.print(
+                        colormap_term_x,
+                        self.term_y(y),
+                        format_args!("{ch} {value}"),
+                    );
+                } else {
+                    screen.print_styled(
+                        colormap_term_x,
+                        self.term_y(y),
+                        value,
+                        Color::Reset,
+                        color(ratio),
+                    );
+                }
+            }
+        }
+
+        let stats = world.stats();
+        let mut stats_lines: Vec<_> = TileType::ALL
+            .iter()
+            .zip(stats.counts)
+            .map(|(ty, count)| format!("{ty:?}: {count}"))
+            .collect();
+        stats_lines.push(format!("max: {}", stats.max_signal_sum.0));
+        stats_lines.push(format!("mean: {:.2}", stats.mean_signal));
+        stats_lines.push(format!("flagged: {}", stats.flagged));
+        let stats_term_x = colormap_term_x + (LEGEND_WIDTH + 2) as u16;
+        for (i, line) in stats_lines.iter().enumerate() {
+            screen.print(stats_term_x, (PADDING_TOP + 1 + i) as u16, line);
+        }
+
+        // probes share a scale so that their plots can be compared
+        let probe_max = world
+            .probes()
+            .iter()
+            .flat_map(|probe| probe.samples())
+            .map(|signal| signal.0)
+            .max()
+            .unwrap_or(0)
+            .max(1);
+        let spark_width = usize::from(term_size.0.saturating_sub(stats_term_x));
+        let mut probe_term_y = (PADDING_TOP + 2 + stats_lines.len()) as u16;
+        for probe in world.probes() {
+            let samples = probe.samples();
+            let last = samples.back().map_or(0, |signal| signal.0);
+            screen.print(
+                stats_term_x,
+                probe_term_y,
+                format_args!("({}, {}): {last}", probe.xy.0, probe.xy.1),
+            );
+            let spark: String = samples
+                .iter()
+                .skip(samples.len().saturating_sub(spark_width))
+                .map(|signal| {
+                    let level =
+                        usize::from(signal.0) * (SPARK_RAMP.len() - 1) / usize::from(probe_max);
+                    SPARK_RAMP[level]
+                })
+                .collect();
+            screen.print(stats_term_x, probe_term_y + 1, spark);
+            probe_term_y += 2;
+        }
+
+        let cursor_tile = &world[self.cursor];
+        let mut cursor_info = format!(
+            "({}, {}) {:?}  signal_sum: {} {}  next_signal: {} {}",
+            self.cursor.0,
+            self.cursor.1,
+            cursor_tile.ty,
+            cursor_tile.signal_sum().0,
+            ChannelsDisplay(cursor_tile.signal_sums()),
+            cursor_tile.next_signal().0,
+            ChannelsDisplay(cursor_tile.next_signals()),
+        );
+        if cursor_tile.ty == TileType::Source {
+            cursor_info.push_str(&format!(
+                "  strength: {}  channel: {}",
+                cursor_tile.strength.0, cursor_tile.channel
+            ));
+        }
+        if cursor_tile.ty == TileType::Conductor {
+            cursor_info.push_str(&format!("  resistance: {}%", cursor_tile.resistance));
+        }
+        if cursor_tile.locked {
+            cursor_info.push_str("  locked");
+        }
+        screen.print(1, x_term_y + 2, cursor_info);
+        screen.print(1, x_term_y + 3, status);
+
+        if overlay == Some(Overlay::Inspect) {
+            // the slot that the next pre_tick overwrites
+            let inspected_offset = world.backlog_offset(now);
+            let overlay_term_x = PADDING_LEFT as u16;
+            let mut overlay_term_y = PADDING_TOP as u16;
+            for (offset, &signals) in cursor_tile.signals().iter().enumerate() {
+                let (fg, bg) = if offset == inspected_offset {
+                    (Color::Black, Color::White)
+                } else {
+                    (Color::White, Color::DarkGrey)
+                };
+                screen.print_styled(
+                    overlay_term_x,
+                    overlay_term_y,
+                    format_args!(" signals[{offset}]: {:<17} ", ChannelsDisplay(signals)),
+                    fg,
+                    bg,
+                );
+                overlay_term_y += 1;
+            }
+            screen.print_styled(
+                overlay_term_x,
+                overlay_term_y,
+                format_args!(
+                    " next_signal: {:<17} ",
+                    ChannelsDisplay(cursor_tile.next_signals())
+                ),
+                Color::White,
+                Color::DarkGrey,
+            );
+            if cursor_tile.weight(&world.rules).0 > 0 {
+                let fuel = match cursor_tile.fuel {
+                    Some(fuel) => fuel.to_string(),
+                    None => "unlimited".to_string(),
+                };
+                screen.print_styled(
+                    overlay_term_x,
+                    overlay_term_y + 1,
+                    format_args!(" fuel: {fuel:<24} "),
+                    Color::White,
+                    Color::DarkGrey,
+                );
+            }
+        }
+
+        screen.flush((self.term_x(self.cursor.0), self.term_y(self.cursor.1)))?;
+
+        Ok(())
     }
+}
 
-    fn term_x(&self, x: usize) -> u16 {
-        (PADDING_LEFT + x * 2) as u16
+fn shade(gradient: &colorgrad::Gradient, f: f64) -> Color {
+    let mut color = gradient.at(f);
+    for comp in [&mut color.r, &mut color.g, &mut color.b] {
+        *comp *= 0.5;
+        *comp += 0.5;
     }
-    fn term_y(&self, y: usize) -> u16 {
-        (PADDING_TOP + self.dim.height - y) as u16
+    Color::Rgb {
+        r: (color.r * 255.0) as u8,
+        g: (color.g * 255.0) as u8,
+        b: (color.b * 255.0) as u8,
     }
+}
+
+/// The widest entry of the help overlay, a right-aligned key and its description.
+fn help_entry_width() -> usize {
+    let key_width = KEYBINDINGS.iter().map(|(key, _)| key.len()).max().unwrap();
+    let description_width = KEYBINDINGS.iter().map(|(_, description)| description.len());
+    key_width + 2 + description_width.max().unwrap()
+}
 
-    fn draw(&self) -> Result<()> {
-        let mut stdout = io::stdout();
-        stdout.queue(terminal::Clear(terminal::ClearType::All))?;
+/// The rows and columns of [`KEYBINDINGS`] that fit on one page of the help overlay,
+/// below a blank row and above a blank row and the footer.
+fn help_page_size((cols, rows): (u16, u16)) -> (usize, usize) {
+    let rows = usize::from(rows).saturating_sub(3).max(1);
+    let width = usize::from(cols).saturating_sub(2) + HELP_COLUMN_GAP;
+    let columns = (width / (help_entry_width() + HELP_COLUMN_GAP)).max(1);
+    (rows, columns)
+}
+
+/// The number of pages the help overlay takes on a terminal of `term_size`.
+fn help_pages(term_size: (u16, u16)) -> usize {
+    let (rows, columns) = help_page_size(term_size);
+    KEYBINDINGS.len().div_ceil(rows * columns)
+}
+
+/// Lightens the channels like [`shade`] and uses them as red, green and blue.
+fn mix_channels(ratios: [f64; CHANNELS]) -> Color {
+    let [r, g, b] = ratios.map(|ratio| {
+        // quantized like the colormap so that tiles are only redrawn on visible changes
+        let level = (ratio * (COLOR_LEVELS - 1) as f64).round() / (COLOR_LEVELS - 1) as f64;
+        ((level * 0.5 + 0.5) * 255.0) as u8
+    });
+    Color::Rgb { r, g, b }
+}
+
+/// Formats the signal of every channel as `[r, g, b]`.
+struct ChannelsDisplay([Signal; CHANNELS]);
+
+impl fmt::Display for ChannelsDisplay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let [r, g, b] = self.0.map(|signal| signal.0);
+        // padding applies to the whole list
+        f.pad(&format!("[{r}, {g}, {b}]"))
+    }
+}
 
-        for y in 0..self.dim.height {
-            stdout
-                .queue(cursor::MoveTo(1, self.term_y(y)))?
-                .queue(style::Print(y))?;
+/// The smallest of 1, 2, 5, 10, 20, 50 and so on that is at least `min`
+/// and puts at most about [`AXIS_LABELS`] labels on an axis spanning `span` tiles.
+fn axis_stride(span: usize, min: usize) -> usize {
+    let min = min.max(span / AXIS_LABELS).max(1);
+    let mut magnitude = 1usize;
+    loop {
+        for step in [1, 2, 5] {
+            let stride = step * magnitude;
+            if stride >= min {
+                return stride;
+            }
         }
+        magnitude *= 10;
+    }
+}
 
-        let x_term_y = (self.dim.height + PADDING_TOP + 2) as u16;
-        for x in (0..self.dim.width).step_by(10) {
-            stdout
-                .queue(cursor::MoveTo(self.term_x(x), x_term_y))?
-                .queue(style::Print(x))?;
+/// How recently a tile was last flagged in `0.0..=1.0`, from cold for tiles idle for
+/// [`ACTIVITY_HORIZON`] ticks or never flagged to hot for those flagged in the last tick.
+fn activity_ratio(last_active_tick: Option<Tick>, now: Tick) -> f64 {
+    match last_active_tick {
+        // `now` is the tick to run next
+        Some(tick) => {
+            let age = now.0.saturating_sub(tick.0 + 1);
+            1.0 - (f64::from(age) / ACTIVITY_HORIZON as f64).min(1.0)
         }
+        None => 0.0,
+    }
+}
 
-        let max_signal_sum = self
-            .tiles
-            .iter()
-            .map(|tile| tile.signal_sum)
-            .max_by_key(|signal| signal.0)
-            .unwrap();
+/// Normalizes an interpolated signal against `max` into `0.0..=1.0` like [`Signal::ratio`].
+fn shown_ratio(signal: f64, max: Signal) -> f64 {
+    if max.0 == 0 {
+        0.0
+    } else {
+        (signal / f64::from(max.0)).min(1.0)
+    }
+}
+
+fn mono_shade(f: f64) -> char {
+    let index = (f * (MONO_RAMP.len() - 1) as f64).round() as usize;
+    MONO_RAMP[index.min(MONO_RAMP.len() - 1)]
+}
+
+/// The [`HEIGHT_RAMP`] character for a signal ratio, or `None` for no signal at all.
+fn height_char(f: f64) -> Option<char> {
+    (f > 0.0).then(|| {
+        let index = (f * HEIGHT_RAMP.len() as f64).ceil() as usize;
+        HEIGHT_RAMP[index.clamp(1, HEIGHT_RAMP.len()) - 1]
+    })
+}
+
+/// The tenth of the maximum a signal ratio falls into, as a digit.
+fn signal_digit(f: f64) -> char {
+    let digit = ((f * 10.0) as u32).min(9);
+    char::from_digit(digit, 10).unwrap()
+}
+
+/// The color of each tile type in the heightmap.
+fn tile_color(ty: TileType) -> Color {
+    match ty {
+        TileType::Air => Color::White,
+        TileType::Bedrock => Color::Grey,
+        TileType::Brick => Color::Red,
+        TileType::Water => Color::Blue,
+        TileType::Diode => Color::Magenta,
+        TileType::Source => Color::Yellow,
+        TileType::Sink => Color::Cyan,
+        TileType::Conductor => Color::Green,
+        TileType::Wall => Color::DarkYellow,
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Shape {
+    Rect,
+    Line,
+}
+
+/// A shape selected between an anchor and the cursor.
+struct Selection {
+    anchor: (usize, usize),
+    shape: Shape,
+}
 
-        for x in 0..self.dim.width {
-            for y in 0..self.dim.height {
-                let tile = &self[(x, y)];
+impl Selection {
+    /// The tiles in the shape spanned by the anchor and `corner`,
+    /// clamped to the grid.
+    fn tiles(&self, corner: (usize, usize), dim: &Dim) -> Vec<(usize, usize)> {
+        let clamp = |(x, y): (usize, usize)| (x.min(dim.width - 1), y.min(dim.height - 1));
+        let (x1, y1) = clamp(self.anchor);
+        let (x2, y2) = clamp(corner);
 
-                stdout
-                    .queue(cursor::MoveTo(self.term_x(x), self.term_y(y)))?
-                    .queue(style::PrintStyledContent(tile.ty.rendered().with(viridis(
-                        tile.signal_sum.0 as f64 / max_signal_sum.0 as f64,
-                    ))))?;
+        match self.shape {
+            Shape::Rect => {
+                let mut tiles = Vec::new();
+                for x in x1.min(x2)..=x1.max(x2) {
+                    for y in y1.min(y2)..=y1.max(y2) {
+                        tiles.push((x, y));
+                    }
+                }
+                tiles
             }
+            Shape::Line => line((x1, y1), (x2, y2)),
         }
+    }
+}
 
-        let colormap_term_x = (PADDING_LEFT + self.dim.width * 2 + PADDING_RIGHT) as u16;
-        for y in 0..self.dim.height {
-            let ratio = y as f64 / self.dim.height as f64;
-            let signal_value = ratio * max_signal_sum.0 as f64;
-            stdout
-                .queue(cursor::MoveTo(colormap_term_x, self.term_y(y)))?
-                .queue(style::PrintStyledContent(
-                    format!("{signal_value:.1}").on(viridis(ratio)),
-                ))?;
+/// Summarizes the signal and tile types of `tiles`.
+fn measure(world: &World, tiles: &[(usize, usize)]) -> String {
+    let mut counts = [0; TileType::ALL.len()];
+    let mut sum = 0u64;
+    for &xy in tiles {
+        let tile = &world[xy];
+        counts[tile.ty as usize] += 1;
+        sum += u64::from(tile.signal_sum().0);
+    }
+
+    let mut summary = format!(
+        "{} tiles, sum {sum}, mean {:.2}",
+        tiles.len(),
+        sum as f64 / tiles.len() as f64
+    );
+    for (ty, count) in TileType::ALL.iter().zip(counts) {
+        if count > 0 {
+            summary.push_str(&format!(", {ty:?} {count}"));
         }
+    }
+    summary
+}
 
-        stdout.queue(cursor::MoveTo(
-            self.term_x(self.cursor.0),
-            self.term_y(self.cursor.1),
-        ))?;
+/// The square of tiles within `radius` of `center` on both axes, clipped to the grid.
+fn brush_area(dim: &Dim, (x, y): (usize, usize), radius: usize) -> Vec<(usize, usize)> {
+    let xs = x.saturating_sub(radius)..=(x + radius).min(dim.width - 1);
+    let ys = y.saturating_sub(radius)..=(y + radius).min(dim.height - 1);
+    ys.flat_map(|y| xs.clone().map(move |x| (x, y))).collect()
+}
 
-        stdout.flush()?;
+/// The 8-connected tiles on the straight line between both ends inclusive,
+/// using Bresenham's algorithm.
+fn line((x1, y1): (usize, usize), (x2, y2): (usize, usize)) -> Vec<(usize, usize)> {
+    let (x1, y1, x2, y2) = (x1 as isize, y1 as isize, x2 as isize, y2 as isize);
+    let (dx, dy) = ((x2 - x1).abs(), -(y2 - y1).abs());
+    let (sx, sy) = ((x2 - x1).signum(), (y2 - y1).signum());
 
-        Ok(())
+    let mut tiles = Vec::new();
+    let (mut x, mut y) = (x1, y1);
+    let mut error = dx + dy;
+    loop {
+        tiles.push((x as usize, y as usize));
+        if (x, y) == (x2, y2) {
+            return tiles;
+        }
+        let doubled_error = error * 2;
+        if doubled_error >= dy {
+            error += dy;
+            x += sx;
+        }
+        if doubled_error <= dx {
+            error += dx;
+            y += sy;
+        }
     }
 }
 
-impl ops::Index<(usize, usize)> for World {
-    type Output = Tile;
+/// A manual edit of tile types, as `(offset, before, after)` for each changed tile.
+struct Edit(Vec<(usize, TileType, TileType)>);
 
-    fn index(&self, (x, y): (usize, usize)) -> &Tile {
-        let offset = self.dim.xy_offset(x, y);
-        &self.tiles[offset]
-    }
+/// Bounded undo/redo stacks of manual edits.
+#[derive(Default)]
+struct History {
+    undo: VecDeque<Edit>,
+    redo: Vec<Edit>,
 }
 
-impl ops::IndexMut<(usize, usize)> for World {
-    fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut Tile {
-        let offset = self.dim.xy_offset(x, y);
-        &mut self.tiles[offset]
+impl History {
+    /// Sets every tile in `tiles` to `ty` as a single undoable edit,
+    /// returning the number of locked tiles skipped.
+    fn paint(
+        &mut self,
+        world: &mut World,
+        tiles: impl IntoIterator<Item = (usize, usize)>,
+        ty: TileType,
+    ) -> usize {
+        self.remap(world, tiles, |_| ty)
+    }
+
+    /// Like [`History::paint`], but sets every tile to `f` applied to its current type.
+    fn remap(
+        &mut self,
+        world: &mut World,
+        tiles: impl IntoIterator<Item = (usize, usize)>,
+        f: impl Fn(TileType) -> TileType,
+    ) -> usize {
+        let mut changes = Vec::new();
+        let mut skipped = 0;
+        for (x, y) in tiles {
+            let before = world[(x, y)].ty;
+            let ty = f(before);
+            if world[(x, y)].locked {
+                skipped += usize::from(before != ty);
+            } else if before != ty {
+                changes.push((world.dim().xy_offset(x, y), before, ty));
+                world.set_type((x, y), ty);
+            }
+        }
+
+        if !changes.is_empty() {
+            self.redo.clear();
+            self.undo.push_back(Edit(changes));
+            if self.undo.len() > HISTORY_LENGTH {
+                self.undo.pop_front();
+            }
+        }
+        skipped
+    }
+
+    fn undo(&mut self, world: &mut World) -> bool {
+        let Some(edit) = self.undo.pop_back() else {
+            return false;
+        };
+        for &(offset, before, _) in &edit.0 {
+            let xy = world.dim().offset_xy(offset);
+            // tiles locked since the edit are skipped like by History::remap
+            if !world[xy].locked {
+                world.set_type(xy, before);
+            }
+        }
+        self.redo.push(edit);
+        true
+    }
+
+    fn redo(&mut self, world: &mut World) -> bool {
+        let Some(edit) = self.redo.pop() else {
+            return false;
+        };
+        for &(offset, _, after) in &edit.0 {
+            let xy = world.dim().offset_xy(offset);
+            if !world[xy].locked {
+                world.set_type(xy, after);
+            }
+        }
+        self.undo.push_back(edit);
+        true
     }
 }
 
-fn viridis(f: f64) -> Color {
-    let mut color = colorgrad::viridis().at(f);
-    for comp in [&mut color.r, &mut color.g, &mut color.b] {
-        *comp *= 0.5;
-        *comp += 0.5;
+/// Writes the signal field of every tick into numbered PGM files.
+struct Recorder {
+    dir: PathBuf,
+    frame: u32,
+    /// The fixed normalization maximum, or `None` to use the running maximum.
+    fixed_max: Option<Signal>,
+    running_max: Signal,
+}
+
+impl Recorder {
+    fn new(dir: PathBuf, fixed_max: Option<Signal>) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir,
+            frame: 0,
+            fixed_max,
+            running_max: Signal(0),
+        })
     }
-    Color::Rgb {
-        r: (color.r * 255.0) as u8,
-        g: (color.g * 255.0) as u8,
-        b: (color.b * 255.0) as u8,
+
+    fn record(&mut self, world: &World) -> io::Result<()> {
+        self.frame += 1;
+        // the maximum only ever grows so that brightness does not flicker between frames
+        self.running_max.0 = self.running_max.0.max(world.max_signal_sum().0);
+        let max = self.fixed_max.unwrap_or(self.running_max);
+
+        let path = self.dir.join(format!("frame_{:05}.pgm", self.frame));
+        let mut w = io::BufWriter::new(fs::File::create(path)?);
+        world.export_pgm_normalized(&mut w, max)?;
+        w.flush()
     }
 }
 
-fn main() -> Result<()> {
-    enable_raw_mode()?;
-    io::stdout().execute(EnterAlternateScreen)?;
+/// Records a frame if recording, stopping the recording on failure.
+/// Returns the message explaining why recording stopped.
+fn record_frame(recorder: &mut Option<Recorder>, world: &World) -> Option<String> {
+    let err = recorder.as_mut()?.record(world).err()?;
+    let dir = recorder.take().unwrap().dir;
+    Some(format!("stopped recording to {}: {err}", dir.display()))
+}
+
+/// Writes the statistics of every tick as CSV rows.
+struct Logger {
+    path: PathBuf,
+    w: io::BufWriter<fs::File>,
+    last_flush: Instant,
+}
 
-    let mut world = World::new(WORLD_WIDTH, WORLD_HEIGHT);
-    for x in 0..world.dim.width {
-        world[(x, 0)].ty = TileType::Bedrock;
+impl Logger {
+    fn new(path: PathBuf) -> io::Result<Self> {
+        let mut w = io::BufWriter::new(fs::File::create(&path)?);
+        write!(w, "tick,total_signal,max_signal")?;
+        for ty in TileType::ALL {
+            write!(w, ",{}", format!("{ty:?}").to_lowercase())?;
+        }
+        writeln!(w, ",flagged")?;
+        Ok(Self {
+            path,
+            w,
+            last_flush: Instant::now(),
+        })
     }
 
-    let mut next_tick_time = Instant::now();
-    let mut current_tick = Tick(0);
+    fn log(&mut self, now: Tick, world: &World) -> io::Result<()> {
+        let stats = world.stats();
+        write!(
+            self.w,
+            "{},{},{}",
+            now.0,
+            world.total_signal(),
+            stats.max_signal_sum.0
+        )?;
+        for count in stats.counts {
+            write!(self.w, ",{count}")?;
+        }
+        writeln!(self.w, ",{}", stats.flagged)?;
 
-    loop {
-        if next_tick_time < Instant::now() {
-            next_tick_time = Instant::now() + TICK_FREQ;
-            world.tick(current_tick);
-            current_tick.0 += 1;
+        // flushing every row would be slow when ticking fast,
+        // but the log should still be followable while running
+        if self.last_flush.elapsed() >= LOG_FLUSH_INTERVAL {
+            self.w.flush()?;
+            self.last_flush = Instant::now();
         }
+        Ok(())
+    }
+}
 
-        world.draw()?;
+/// Logs a tick if logging, stopping the log on failure.
+/// Returns the message explaining why logging stopped.
+fn log_tick(logger: &mut Option<Logger>, now: Tick, world: &World) -> Option<String> {
+    let err = logger.as_mut()?.log(now, world).err()?;
+    let path = logger.take().unwrap().path;
+    Some(format!("stopped logging to {}: {err}", path.display()))
+}
 
-        if event::poll(next_tick_time.saturating_duration_since(Instant::now()))? {
-            match event::read()? {
-                Event::Key(KeyEvent {
-                    code: KeyCode::Char('q'),
-                    ..
-                }) => break,
-                Event::Key(KeyEvent {
-                    code: KeyCode::Char(ch @ ('h' | 'l' | 'j' | 'k')),
-                    ..
-                }) => {
-                    let (cursor, limit, delta) = match ch {
-                        'h' => (&mut world.cursor.0, world.dim.width, -1),
-                        'l' => (&mut world.cursor.0, world.dim.width, 1),
-                        'j' => (&mut world.cursor.1, world.dim.height, -1),
-                        'k' => (&mut world.cursor.1, world.dim.height, 1),
-                        _ => unreachable!(),
-                    };
-                    match (*cursor).checked_add_signed(delta) {
-                        None => {}
-                        Some(new_value) if new_value >= limit => {}
-                        Some(new_value) => *cursor = new_value,
+fn record_input(recorder: &mut Option<InputRecorder>, now: Tick, event: &Event) -> Option<String> {
+    let err = recorder.as_mut()?.record(now.0, event).err()?;
+    let path = recorder.take().unwrap().path;
+    Some(format!(
+        "stopped recording input to {}: {err}",
+        path.display()
+    ))
+}
+
+fn export(world: &World, path: &str) -> io::Result<()> {
+    let mut w = io::BufWriter::new(fs::File::create(path)?);
+    world.export_pgm(&mut w)?;
+    w.flush()
+}
+
+struct Args {
+    width: usize,
+    height: usize,
+    seed: u64,
+    save_path: String,
+    screenshot_path: String,
+    #[cfg(feature = "snapshot")]
+    snapshot_path: String,
+    /// The snapshot to resume from, overriding the world and its settings.
+    #[cfg(feature = "snapshot")]
+    resume_path: Option<String>,
+    /// The layout to load, read as JSON if it ends with `.json`,
+    /// or `-` to read a layout without header from stdin.
+    load_path: Option<String>,
+    export_path: String,
+    json_export_path: String,
+    diagonal: bool,
+    /// Whether ticking uses no randomness at all, see [`World::deterministic`].
+    deterministic: bool,
+    wrap: bool,
+    /// Whether new and cleared worlds get a bedrock floor.
+    floor: bool,
+    /// Whether to run the ticks headless as fast as possible and report how long they took.
+    bench: bool,
+    /// The ticks `--bench` runs unless `--max-ticks` is given as well.
+    bench_ticks: u32,
+    /// How many ticks to run before exporting the signal and quitting.
+    max_ticks: Option<u32>,
+    colormap: Colormap,
+    /// The pattern placed on new worlds, unless a layout is loaded.
+    start_pattern: Option<StartPattern>,
+    mono: bool,
+    /// Whether to draw row 0 at the top, see `View::flip_y`.
+    flip_y: bool,
+    /// Whether to start out interpolating between ticks, see `View::smooth`.
+    smooth: bool,
+    /// Only label every this many tiles on both axes with `V`, to keep labels readable.
+    label_stride: usize,
+    /// The strides of the axis labels, or `None` to adapt them to the viewport.
+    x_axis_stride: Option<usize>,
+    y_axis_stride: Option<usize>,
+    sink_drain: f64,
+    /// The ceiling of every `signal_sum`, or `None` to only saturate at the maximum.
+    signal_ceiling: Option<u16>,
+    /// The tile rules from the config file, or `None` to keep the built-in ones.
+    rules: Option<Rules>,
+    decay: f64,
+    random_tick_rate: usize,
+    flag_rate: f64,
+    backlog_length: usize,
+    backlog_unit: u32,
+    record_dir: Option<PathBuf>,
+    record_max: Option<u16>,
+    log_path: Option<PathBuf>,
+    /// The file to append every key and mouse event to, for replaying with `--replay`.
+    record_input_path: Option<PathBuf>,
+    /// The input log to feed back instead of waiting for the terminal,
+    /// which needs the same terminal size as the recording for mouse events to match.
+    replay_path: Option<PathBuf>,
+    /// Tick without waiting for the tick timer until the replay is done.
+    replay_fast: bool,
+    tick_freq: Duration,
+}
+
+impl Args {
+    fn parse() -> Result<Self> {
+        let argv: Vec<_> = env::args().skip(1).collect();
+        // the config file only provides the defaults for the other flags
+        let config = match argv.iter().position(|flag| flag == "--config") {
+            Some(i) => {
+                let path: PathBuf = flag_value("--config", argv.get(i + 1).cloned())?;
+                Config::load(&path)?
+            }
+            None if Path::new(CONFIG_PATH).exists() => Config::load(Path::new(CONFIG_PATH))?,
+            None => Config::default(),
+        };
+
+        let mut args = Self {
+            width: config.width.unwrap_or(WORLD_WIDTH),
+            height: config.height.unwrap_or(WORLD_HEIGHT),
+            seed: config.seed.unwrap_or_else(rand::random),
+            save_path: SAVE_PATH.to_string(),
+            screenshot_path: SCREENSHOT_PATH.to_string(),
+            #[cfg(feature = "snapshot")]
+            snapshot_path: SNAPSHOT_PATH.to_string(),
+            #[cfg(feature = "snapshot")]
+            resume_path: None,
+            load_path: None,
+            export_path: EXPORT_PATH.to_string(),
+            json_export_path: JSON_EXPORT_PATH.to_string(),
+            diagonal: config.diagonal.unwrap_or(false),
+            deterministic: config.deterministic.unwrap_or(false),
+            wrap: config.wrap.unwrap_or(false),
+            floor: config.floor.unwrap_or(true),
+            bench: false,
+            bench_ticks: DEFAULT_BENCH_TICKS,
+            max_ticks: None,
+            colormap: match &config.colormap {
+                Some(name) => parse_colormap(name)?,
+                None => Colormap::default(),
+            },
+            start_pattern: match &config.start_pattern {
+                Some(name) => Some(parse_start_pattern(name)?),
+                None => None,
+            },
+            mono: config.mono.unwrap_or(false),
+            smooth: config.smooth.unwrap_or(false),
+            flip_y: config.flip_y.unwrap_or(false),
+            label_stride: config.label_stride.unwrap_or(1),
+            x_axis_stride: config.x_axis_stride,
+            y_axis_stride: config.y_axis_stride,
+            sink_drain: config.sink_drain.unwrap_or(DEFAULT_SINK_DRAIN),
+            signal_ceiling: config.signal_ceiling,
+            rules: config.rules()?,
+            decay: config.decay.unwrap_or(1.0),
+            random_tick_rate: config
+                .random_tick_rate
+                .unwrap_or(DEFAULT_RANDOM_TICK_PERCENTAGE),
+            flag_rate: config.flag_rate.unwrap_or(DEFAULT_FLAG_RATE),
+            backlog_length: config
+                .backlog_length
+                .unwrap_or(DEFAULT_SIGNAL_BACKLOG_LENGTH),
+            backlog_unit: config.backlog_unit.unwrap_or(DEFAULT_SIGNAL_BACKLOG_UNIT.0),
+            record_dir: None,
+            record_max: None,
+            log_path: None,
+            record_input_path: None,
+            replay_path: None,
+            replay_fast: false,
+            tick_freq: config
+                .tick_interval
+                .map_or(TICK_FREQ, Duration::from_millis),
+        };
+
+        let mut argv = argv.into_iter().peekable();
+        while let Some(flag) = argv.next() {
+            match flag.as_str() {
+                // already read before the other flags
+                "--config" => _ = argv.next(),
+                "--width" => args.width = flag_value(&flag, argv.next())?,
+                "--height" => args.height = flag_value(&flag, argv.next())?,
+                "--seed" => args.seed = flag_value(&flag, argv.next())?,
+                "--save" => args.save_path = flag_value(&flag, argv.next())?,
+                "--screenshot" => args.screenshot_path = flag_value(&flag, argv.next())?,
+                #[cfg(feature = "snapshot")]
+                "--snapshot" => args.snapshot_path = flag_value(&flag, argv.next())?,
+                #[cfg(feature = "snapshot")]
+                "--resume" => args.resume_path = Some(flag_value(&flag, argv.next())?),
+                "--load" => args.load_path = Some(flag_value(&flag, argv.next())?),
+                "--export" => args.export_path = flag_value(&flag, argv.next())?,
+                "--export-json" => args.json_export_path = flag_value(&flag, argv.next())?,
+                "--diagonal" => args.diagonal = true,
+                "--deterministic" => args.deterministic = true,
+                "--wrap" => args.wrap = true,
+                "--no-floor" => args.floor = false,
+                "--bench" => {
+                    args.bench = true;
+                    // the tick count is optional
+                    if let Some(ticks) = argv.next_if(|value| value.parse::<u32>().is_ok()) {
+                        args.bench_ticks = ticks.parse()?;
                     }
                 }
-                Event::Key(KeyEvent {
-                    code: KeyCode::Char(ch @ ('0' | '9' | '1')),
-                    ..
-                }) => {
-                    let tile = match ch {
-                        '0' => TileType::Air,
-                        '9' => TileType::Bedrock,
-                        '1' => TileType::Brick,
-                        _ => unreachable!(),
-                    };
-                    let cursor = world.cursor;
-                    world[cursor].ty = tile;
+                "--max-ticks" => args.max_ticks = Some(flag_value(&flag, argv.next())?),
+                "--mono" => args.mono = true,
+                "--smooth" => args.smooth = true,
+                "--flip-y" => args.flip_y = true,
+                "--label-stride" => args.label_stride = flag_value(&flag, argv.next())?,
+                "--x-axis-stride" => args.x_axis_stride = Some(flag_value(&flag, argv.next())?),
+                "--y-axis-stride" => args.y_axis_stride = Some(flag_value(&flag, argv.next())?),
+                "--sink-drain" => args.sink_drain = flag_value(&flag, argv.next())?,
+                "--signal-ceiling" => args.signal_ceiling = Some(flag_value(&flag, argv.next())?),
+                "--decay" => args.decay = flag_value(&flag, argv.next())?,
+                "--random-tick-rate" => args.random_tick_rate = flag_value(&flag, argv.next())?,
+                "--flag-rate" => args.flag_rate = flag_value(&flag, argv.next())?,
+                "--backlog-length" => args.backlog_length = flag_value(&flag, argv.next())?,
+                "--backlog-unit" => args.backlog_unit = flag_value(&flag, argv.next())?,
+                "--record" => args.record_dir = Some(flag_value(&flag, argv.next())?),
+                "--record-max" => args.record_max = Some(flag_value(&flag, argv.next())?),
+                "--log" => args.log_path = Some(flag_value(&flag, argv.next())?),
+                "--record-input" => {
+                    args.record_input_path = Some(flag_value(&flag, argv.next())?);
                 }
-                Event::Key(KeyEvent {
-                    code: KeyCode::Char('t'),
-                    ..
-                }) => next_tick_time = Instant::now(),
-                _ => {}
+                "--replay" => args.replay_path = Some(flag_value(&flag, argv.next())?),
+                "--replay-fast" => args.replay_fast = true,
+                "--tick-interval" => {
+                    args.tick_freq = Duration::from_millis(flag_value(&flag, argv.next())?);
+                }
+                "--start-pattern" => {
+                    let name: String = flag_value(&flag, argv.next())?;
+                    args.start_pattern = Some(parse_start_pattern(&name)?);
+                }
+                "--colormap" => {
+                    let name: String = flag_value(&flag, argv.next())?;
+                    args.colormap = parse_colormap(&name)?;
+                }
+                _ => bail!("unknown argument {flag:?}"),
             }
         }
+
+        if args.width == 0 || args.height == 0 {
+            bail!(
+                "the world must be at least 1x1, got {}x{}",
+                args.width,
+                args.height
+            );
+        }
+        if args.width.checked_mul(args.height).is_none() {
+            bail!("the world {}x{} is too large", args.width, args.height);
+        }
+        if !(0.0..=1.0).contains(&args.sink_drain) {
+            bail!(
+                "the sink drain must be within 0..=1, got {}",
+                args.sink_drain
+            );
+        }
+        if args.label_stride == 0 {
+            bail!("the label stride must be at least 1");
+        }
+        if args.x_axis_stride == Some(0) || args.y_axis_stride == Some(0) {
+            bail!("the axis strides must be at least 1");
+        }
+        if args.signal_ceiling == Some(0) {
+            bail!("the signal ceiling must be at least 1");
+        }
+        if !(0.0..=1.0).contains(&args.decay) {
+            bail!("the decay must be within 0..=1, got {}", args.decay);
+        }
+        if !(1..=MAX_SIGNAL_BACKLOG_LENGTH).contains(&args.backlog_length) {
+            bail!(
+                "the backlog length must be within 1..={MAX_SIGNAL_BACKLOG_LENGTH}, got {}",
+                args.backlog_length
+            );
+        }
+        if args.backlog_unit == 0 {
+            bail!("the backlog unit must be at least 1 tick");
+        }
+        if !(MIN_TICK_FREQ..=MAX_TICK_FREQ).contains(&args.tick_freq) {
+            bail!(
+                "the tick interval must be within {}..={}ms, got {}ms",
+                MIN_TICK_FREQ.as_millis(),
+                MAX_TICK_FREQ.as_millis(),
+                args.tick_freq.as_millis()
+            );
+        }
+        if args.random_tick_rate > 100 {
+            bail!(
+                "the random tick rate must be a percentage within 0..=100, got {}",
+                args.random_tick_rate
+            );
+        }
+        if !(0.0..=1.0).contains(&args.flag_rate) {
+            bail!("the flag rate must be within 0..=1, got {}", args.flag_rate);
+        }
+
+        Ok(args)
     }
+}
 
-    disable_raw_mode()?;
-    io::stdout().execute(LeaveAlternateScreen)?;
+fn flag_value<T>(flag: &str, value: Option<String>) -> Result<T>
+where
+    T: FromStr,
+    T::Err: error::Error + Send + Sync + 'static,
+{
+    let value = value.with_context(|| format!("{flag} requires a value"))?;
+    value
+        .parse()
+        .with_context(|| format!("invalid value {value:?} for {flag}"))
+}
 
-    Ok(())
+fn parse_colormap(name: &str) -> Result<Colormap> {
+    Colormap::from_name(name).with_context(|| {
+        let names: Vec<_> = Colormap::ALL.iter().map(|c| c.name()).collect();
+        format!(
+            "unknown colormap {name:?}, expected one of {}",
+            names.join(", ")
+        )
+    })
+}
+
+fn parse_start_pattern(name: &str) -> Result<StartPattern> {
+    StartPattern::from_name(name).with_context(|| {
+        let names: Vec<_> = StartPattern::ALL.iter().map(|p| p.name()).collect();
+        format!(
+            "unknown start pattern {name:?}, expected one of {}",
+            names.join(", ")
+        )
+    })
+}
+
+/// Turns the bottom row into bedrock.
+fn lay_floor(world: &mut World) {
+    for x in 0..world.dim().width {
+        // set_type flags the floor in case the rules make bedrock emit
+        if !world[(x, 0)].locked {
+            world.set_type((x, 0), TileType::Bedrock);
+        }
+    }
+}
+
+/// The type a tile of type `ty` becomes when swapped by [`SWAPS`].
+fn swap(ty: TileType) -> TileType {
+    SWAPS
+        .into_iter()
+        .find_map(|(a, b)| {
+            if ty == a {
+                Some(b)
+            } else if ty == b {
+                Some(a)
+            } else {
+                None
+            }
+        })
+        .unwrap_or(ty)
+}
+
+/// The message after painting skipped `skipped` locked tiles, if any.
+fn skipped_locked(skipped: usize) -> Option<String> {
+    (skipped > 0).then(|| format!("skipped {skipped} locked tiles, unlock them with K"))
+}
+
+fn main() -> Result<()> {
+    let mut args = Args::parse()?;
+    let mut replay = args.replay_path.as_deref().map(Replay::load).transpose()?;
+    if let Some(replay) = &replay {
+        // the events only reproduce the session with the same randomness
+        args.seed = replay.seed;
+    }
+
+    let mut world = match &args.load_path {
+        Some(path) if path == "-" => World::load_ascii(io::stdin().lock(), args.seed)
+            .context("cannot load the layout from stdin")?,
+        Some(path) => {
+            let file = fs::File::open(path).with_context(|| format!("cannot open {path}"))?;
+            let r = io::BufReader::new(file);
+            if path.ends_with(".json") {
+                World::load_json(r, args.seed)
+            } else {
+                World::load(r, args.seed)
+            }
+            .with_context(|| format!("cannot load {path}"))?
+        }
+        None => {
+            let mut world = World::new(args.width, args.height, args.seed);
+            if args.floor {
+                lay_floor(&mut world);
+            }
+            if let Some(pattern) = args.start_pattern {
+                pattern.lay(&mut world);
+            }
+            world
+        }
+    };
+
+    world.diagonal = args.diagonal;
+    world.deterministic = args.deterministic;
+    world.wrap = args.wrap;
+    world.sink_drain = args.sink_drain;
+    if let Some(ceiling) = args.signal_ceiling {
+        world.signal_ceiling = Signal(ceiling);
+    }
+    world.decay = args.decay;
+    world.random_tick_percentage = args.random_tick_rate;
+    world.flag_rate = args.flag_rate;
+    world.set_backlog(args.backlog_length, Tick(args.backlog_unit));
+    if let Some(rules) = &args.rules {
+        world.rules = rules.clone();
+        // the tiles flagged so far were picked by the built-in rules
+        world.reseed_flags();
+    }
+
+    #[cfg(feature = "snapshot")]
+    let (cursor, mut current_tick) = match &args.resume_path {
+        Some(path) => {
+            // the snapshot replaces the settings of the world as well
+            let snapshot = Snapshot::load(path)?;
+            world = snapshot.world;
+            (snapshot.cursor, snapshot.current_tick)
+        }
+        None => ((0, 0), Tick(0)),
+    };
+    #[cfg(not(feature = "snapshot"))]
+    let (cursor, mut current_tick) = ((0, 0), Tick(0));
+
+    if args.bench {
+        let ticks = args.max_ticks.unwrap_or(args.bench_ticks);
+        let start = Instant::now();
+        world.tick_n(current_tick, ticks);
+        let elapsed = start.elapsed();
+
+        println!(
+            "{ticks} ticks in {elapsed:?} ({:.1} ticks/s), total signal {}",
+            f64::from(ticks) / elapsed.as_secs_f64(),
+            world.total_signal(),
+        );
+        // like the interactive run, which exports once it reaches --max-ticks
+        if args.max_ticks.is_some() {
+            export(&world, &args.export_path)
+                .with_context(|| format!("cannot export to {}", args.export_path))?;
+            println!("exported to {}", args.export_path);
+        }
+        return Ok(());
+    }
+
+    let mut recorder = match args.record_dir.clone() {
+        Some(dir) => Some(
+            Recorder::new(dir.clone(), args.record_max.map(Signal))
+                .with_context(|| format!("cannot record to {}", dir.display()))?,
+        ),
+        None => None,
+    };
+    let mut logger = match args.log_path.clone() {
+        Some(path) => Some(
+            Logger::new(path.clone())
+                .with_context(|| format!("cannot log to {}", path.display()))?,
+        ),
+        None => None,
+    };
+    let mut input_recorder = match args.record_input_path.clone() {
+        Some(path) => Some(
+            InputRecorder::new(path.clone(), args.seed)
+                .with_context(|| format!("cannot record input to {}", path.display()))?,
+        ),
+        None => None,
+    };
+
+    let (term_cols, term_rows) = terminal::size()?;
+    let (full_cols, full_rows) = Viewport::term_size_for(world.dim().width, world.dim().height);
+    let mut message = (usize::from(term_cols) < full_cols || usize::from(term_rows) < full_rows)
+        .then(|| {
+            format!(
+                "world needs {full_cols}x{full_rows} to fit, terminal is {term_cols}x{term_rows}"
+            )
+        })
+        .or_else(|| (!world.comments.is_empty()).then(|| world.comments.join(" / ")));
+
+    let terminal = TerminalGuard::enter()?;
+
+    let mut tick_freq = args.tick_freq;
+    // stepped ticks and those run from the prompt count as well
+    let last_tick = args
+        .max_ticks
+        .map(|ticks| current_tick.0.saturating_add(ticks));
+    let start_time = Instant::now();
+    let mut last_tick_time = Instant::now();
+    let mut next_tick_time = Instant::now();
+    let mut paused = false;
+    let mut step_requested = false;
+    let mut tick_time = Duration::ZERO;
+    let mut draw_time = Duration::ZERO;
+    // the digits typed so far while prompting for a number of ticks to run
+    let mut step_prompt = None::<String>;
+    let mut overlay = None::<Overlay>;
+    let mut clear_requested = false;
+    let mut top_requested = false;
+    let mut fill_requested = false;
+    // the repeat count typed before a cursor motion
+    let mut count = None::<usize>;
+    let mut brush = TileType::Brick;
+    // how far the brush extends from the cursor in each direction
+    let mut brush_radius = 0;
+    let mut burst = DEFAULT_BURST;
+    // whether cursor motions stamp the brush along their path
+    let mut pen_down = false;
+    let mut selection = None::<Selection>;
+    let mut history = History::default();
+    let mut screen = Screen::default();
+    let mut view = View {
+        cursor,
+        mono: args.mono,
+        smooth: args.smooth,
+        flip_y: args.flip_y,
+        x_axis_stride: args.x_axis_stride,
+        y_axis_stride: args.y_axis_stride,
+        against_ceiling: args.signal_ceiling.is_some(),
+        ..View::default()
+    };
+    view.set_colormap(args.colormap);
+
+    loop {
+        if last_tick.is_some_and(|last| current_tick.0 >= last) {
+            drop(terminal);
+            export(&world, &args.export_path)
+                .with_context(|| format!("cannot export to {}", args.export_path))?;
+            println!(
+                "ran {} ticks, exported to {}",
+                args.max_ticks.unwrap(),
+                args.export_path
+            );
+            break;
+        }
+
+        let replaying_fast = args.replay_fast && replay.as_ref().is_some_and(|r| !r.is_done());
+        // events recorded before this tick have to be handled first
+        let awaiting_replay = replay.as_ref().is_some_and(|r| r.is_due(current_tick.0));
+        if !awaiting_replay
+            && (step_requested || (!paused && (replaying_fast || next_tick_time < Instant::now())))
+        {
+            step_requested = false;
+            last_tick_time = Instant::now();
+            next_tick_time = last_tick_time + tick_freq;
+            view.remember_signals(&world);
+            world.tick(current_tick);
+            tick_time = last_tick_time.elapsed();
+            if let Some(stopped) = log_tick(&mut logger, current_tick, &world) {
+                message = Some(stopped);
+            }
+            current_tick.0 += 1;
+            if let Some(stopped) = record_frame(&mut recorder, &world) {
+                message = Some(stopped);
+            }
+        }
+
+        let elapsed = start_time.elapsed().as_secs();
+        // what needs attention comes first, since the row is cut off at the terminal edge
+        let mut status = String::new();
+        if paused {
+            status.push_str("PAUSED  ");
+        }
+        if let Some(input) = &step_prompt {
+            status.push_str(&format!("ticks to run: {input}  "));
+        }
+        if let Some(count) = count {
+            status.push_str(&format!("count: {count}  "));
+        }
+        if let Some(message) = &message {
+            status.push_str(message);
+            status.push_str("  ");
+        }
+        status.push_str(&format!(
+            "seed: {}  ticks: {} (next slot {})  elapsed: {}:{:02}:{:02}  \
+             tick interval: {}ms  flag rate: {:.1}  brush: {:?} {:?}  tick: {:.2}ms  draw: {:.2}ms",
+            args.seed,
+            current_tick.0,
+            world.backlog_offset(current_tick),
+            elapsed / 3600,
+            elapsed / 60 % 60,
+            elapsed % 60,
+            tick_freq.as_millis(),
+            world.flag_rate,
+            brush,
+            brush.rendered(),
+            tick_time.as_secs_f64() * 1000.0,
+            draw_time.as_secs_f64() * 1000.0,
+        ));
+        if brush_radius > 0 {
+            let size = brush_radius * 2 + 1;
+            status.push_str(&format!("  brush size: {size}x{size}"));
+        }
+        status.push_str(&format!("  burst: {}", burst.0));
+        if world.diagonal {
+            status.push_str("  diagonal");
+        }
+        if world.deterministic {
+            status.push_str("  deterministic");
+        }
+        if !world.random_ticks_enabled {
+            status.push_str("  emitters paused");
+        }
+        if world.wrap {
+            status.push_str("  wrap");
+        }
+        if let Some(selection) = &selection {
+            let shape = match selection.shape {
+                Shape::Rect => "rectangle",
+                Shape::Line => "line",
+            };
+            status.push_str(&format!(
+                "  selecting {shape} from ({}, {}): {}",
+                selection.anchor.0,
+                selection.anchor.1,
+                measure(&world, &selection.tiles(view.cursor, world.dim())),
+            ));
+        }
+        if pen_down {
+            status.push_str("  pen down");
+        }
+        if let Some(recorder) = &recorder {
+            status.push_str(&format!(
+                "  recording to {} (frame {})",
+                recorder.dir.display(),
+                recorder.frame
+            ));
+        }
+        if let Some(logger) = &logger {
+            status.push_str(&format!("  logging to {}", logger.path.display()));
+        }
+        if let Some(recorder) = &input_recorder {
+            status.push_str(&format!("  recording input to {}", recorder.path.display()));
+        }
+        if replay.as_ref().is_some_and(|r| !r.is_done()) {
+            status.push_str("  replaying");
+        }
+        view.adapt_running_max(world.max_signal_sum());
+        let term_size = terminal::size()?;
+        view.fit_viewport(world.dim(), term_size);
+        // paused worlds show their current signals rather than staying in between
+        let progress = if paused {
+            1.0
+        } else {
+            let remaining = next_tick_time.saturating_duration_since(Instant::now());
+            1.0 - (remaining.as_secs_f64() / tick_freq.as_secs_f64()).min(1.0)
+        };
+        let draw_start = Instant::now();
+        view.draw(
+            &mut screen,
+            term_size,
+            &world,
+            &status,
+            current_tick,
+            overlay,
+            progress,
+        )?;
+        draw_time = draw_start.elapsed();
+
+        let poll_timeout = if replaying_fast {
+            Duration::ZERO
+        } else if paused {
+            MAX_TICK_FREQ
+        } else if view.smooth {
+            next_tick_time
+                .saturating_duration_since(Instant::now())
+                .min(SMOOTH_FRAME_INTERVAL)
+        } else {
+            next_tick_time.saturating_duration_since(Instant::now())
+        };
+        let event = match replay.as_mut().and_then(|r| r.next_due(current_tick.0)) {
+            Some(event) => Some(event),
+            None if event::poll(poll_timeout)? => Some(event::read()?),
+            None => None,
+        };
+        if let Some(event) = event {
+            let moved = matches!(
+                event,
+                Event::Mouse(MouseEvent {
+                    kind: MouseEventKind::Moved,
+                    ..
+                })
+            );
+            if matches!(event, Event::Key(_) | Event::Mouse(_)) && !moved {
+                if let Some(stopped) = record_input(&mut input_recorder, current_tick, &event) {
+                    message = Some(stopped);
+                }
+            }
+            // a clear is only confirmed by the key right after it
+            let mut confirming_clear = false;
+            // likewise, g only jumps to the top if pressed twice in a row
+            let mut confirming_top = false;
+            let mut confirming_fill = false;
+            // counts only carry over to the next key, which either extends or uses them
+            let mut pending_count = None;
+            if matches!(event, Event::Key(_)) {
+                message = None;
+                confirming_clear = mem::take(&mut clear_requested);
+                confirming_top = mem::take(&mut top_requested);
+                confirming_fill = mem::take(&mut fill_requested);
+                pending_count = count.take();
+            }
+
+            match event {
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char(' '),
+                    ..
+                }) if matches!(overlay, Some(Overlay::Help { page }) if page + 1 < help_pages(term_size)) => {
+                    if let Some(Overlay::Help { page }) = &mut overlay {
+                        *page += 1;
+                    }
+                }
+                Event::Key(_) if overlay.is_some() => overlay = None,
+                Event::Key(KeyEvent { code, .. }) if step_prompt.is_some() => match code {
+                    KeyCode::Char(ch @ '0'..='9') => step_prompt.as_mut().unwrap().push(ch),
+                    KeyCode::Backspace => {
+                        step_prompt.as_mut().unwrap().pop();
+                    }
+                    KeyCode::Enter => {
+                        let input = step_prompt.take().unwrap();
+                        message = Some(match input.parse::<u32>() {
+                            Ok(ticks) => {
+                                let ticks = last_tick
+                                    .map_or(ticks, |last| ticks.min(last - current_tick.0));
+                                // run in one go and only draw the final state
+                                let mut stopped = None;
+                                if logger.is_none() && recorder.is_none() {
+                                    let start = Instant::now();
+                                    current_tick = world.tick_n(current_tick, ticks);
+                                    tick_time = start.elapsed() / ticks.max(1);
+                                } else {
+                                    // every tick is logged or recorded on its own
+                                    for _ in 0..ticks {
+                                        let start = Instant::now();
+                                        world.tick(current_tick);
+                                        tick_time = start.elapsed();
+                                        stopped =
+                                            stopped.or(log_tick(&mut logger, current_tick, &world));
+                                        current_tick.0 += 1;
+                                        stopped = stopped.or(record_frame(&mut recorder, &world));
+                                    }
+                                }
+                                stopped.unwrap_or_else(|| format!("ran {ticks} ticks"))
+                            }
+                            Err(err) => format!("invalid tick count {input:?}: {err}"),
+                        });
+                    }
+                    KeyCode::Esc => step_prompt = None,
+                    _ => {}
+                },
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('q'),
+                    ..
+                }) => break,
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char(ch @ ('h' | 'l' | 'j' | 'k')),
+                    ..
+                }) => {
+                    let motion = match ch {
+                        'h' => (-1, 0),
+                        'l' => (1, 0),
+                        'j' => (0, -1),
+                        'k' => (0, 1),
+                        _ => unreachable!(),
+                    };
+                    let from = view.cursor;
+                    view.move_cursor(world.dim(), motion, pending_count.unwrap_or(1));
+                    if pen_down {
+                        let tiles: Vec<_> = line(from, view.cursor)
+                            .into_iter()
+                            .flat_map(|xy| brush_area(world.dim(), xy, brush_radius))
+                            .collect();
+                        message = skipped_locked(history.paint(&mut world, tiles, brush));
+                    }
+                }
+                // jumps move as far as the grid edges allow
+                Event::Key(KeyEvent {
+                    code: KeyCode::Home,
+                    ..
+                }) => view.move_cursor(world.dim(), (-1, 0), usize::MAX),
+                Event::Key(KeyEvent {
+                    code: KeyCode::End, ..
+                }) => view.move_cursor(world.dim(), (1, 0), usize::MAX),
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('g'),
+                    ..
+                }) => {
+                    if confirming_top {
+                        view.move_cursor(world.dim(), (0, 1), usize::MAX);
+                    } else {
+                        top_requested = true;
+                    }
+                }
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('G'),
+                    ..
+                }) => view.move_cursor(world.dim(), (0, -1), usize::MAX),
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char(ch @ '0'..='9'),
+                    ..
+                }) if ch != '0' || pending_count.is_some() => {
+                    let digit = ch.to_digit(10).unwrap() as usize;
+                    count = Some(
+                        pending_count
+                            .unwrap_or(0)
+                            .saturating_mul(10)
+                            .saturating_add(digit),
+                    );
+                }
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char(ch @ ('a' | 'r' | 'b' | '~' | 'd' | 's' | 'S' | 'e' | 'X')),
+                    modifiers,
+                    ..
+                }) if !modifiers.contains(KeyModifiers::CONTROL) => {
+                    let tile = match ch {
+                        'a' => TileType::Air,
+                        'r' => TileType::Bedrock,
+                        'b' => TileType::Brick,
+                        '~' => TileType::Water,
+                        'd' => TileType::Diode,
+                        's' => TileType::Source,
+                        'S' => TileType::Sink,
+                        'e' => TileType::Conductor,
+                        'X' => TileType::Wall,
+                        _ => unreachable!(),
+                    };
+                    brush = tile;
+                }
+                Event::Key(KeyEvent {
+                    code: code @ (KeyCode::Tab | KeyCode::BackTab),
+                    ..
+                }) => {
+                    let len = TileType::ALL.len();
+                    let i = TileType::ALL.iter().position(|&ty| ty == brush).unwrap();
+                    brush = match code {
+                        KeyCode::Tab => TileType::ALL[(i + 1) % len],
+                        KeyCode::BackTab => TileType::ALL[(i + len - 1) % len],
+                        _ => unreachable!(),
+                    };
+                }
+                Event::Key(KeyEvent {
+                    code: KeyCode::Enter,
+                    ..
+                }) => {
+                    let tiles = brush_area(world.dim(), view.cursor, brush_radius);
+                    message = skipped_locked(history.paint(&mut world, tiles, brush));
+                }
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('x'),
+                    ..
+                }) => {
+                    let tiles = brush_area(world.dim(), view.cursor, brush_radius);
+                    message = skipped_locked(history.paint(&mut world, tiles, TileType::Air));
+                }
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char(ch @ ('<' | '>')),
+                    ..
+                }) => {
+                    brush_radius = match ch {
+                        '<' => brush_radius.saturating_sub(1),
+                        '>' => (brush_radius + 1).min(MAX_BRUSH_RADIUS),
+                        _ => unreachable!(),
+                    };
+                }
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char(ch @ ('v' | 'L')),
+                    ..
+                }) => match selection.take() {
+                    None => {
+                        let shape = match ch {
+                            'v' => Shape::Rect,
+                            'L' => Shape::Line,
+                            _ => unreachable!(),
+                        };
+                        selection = Some(Selection {
+                            anchor: view.cursor,
+                            shape,
+                        })
+                    }
+                    Some(selection) => {
+                        let tiles = selection.tiles(view.cursor, world.dim());
+                        message = skipped_locked(history.paint(&mut world, tiles, brush));
+                    }
+                },
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('E'),
+                    ..
+                }) => match selection.take() {
+                    None => message = Some("select a region with v or L first".to_string()),
+                    Some(selection) => {
+                        let tiles = selection.tiles(view.cursor, world.dim());
+                        message = skipped_locked(history.remap(&mut world, tiles, swap));
+                    }
+                },
+                Event::Key(KeyEvent {
+                    code: KeyCode::Esc, ..
+                }) => selection = None,
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('f'),
+                    ..
+                }) if world[view.cursor].ty != brush => {
+                    let tiles = world.region(view.cursor);
+                    message = skipped_locked(history.paint(&mut world, tiles, brush));
+                }
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('u'),
+                    ..
+                }) => {
+                    let undone = history.undo(&mut world);
+                    message = (!undone).then(|| "nothing to undo".to_string());
+                }
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('r'),
+                    modifiers,
+                    ..
+                }) if modifiers.contains(KeyModifiers::CONTROL) => {
+                    let redone = history.redo(&mut world);
+                    message = (!redone).then(|| "nothing to redo".to_string());
+                }
+                Event::Key(KeyEvent {
+                    code: code @ (KeyCode::Left | KeyCode::Right | KeyCode::Up | KeyCode::Down),
+                    ..
+                }) => {
+                    let dim = world.dim();
+                    let (width, height) = match code {
+                        KeyCode::Left => (dim.width.saturating_sub(1).max(1), dim.height),
+                        KeyCode::Right => (dim.width.saturating_add(1), dim.height),
+                        KeyCode::Down => (dim.width, dim.height.saturating_sub(1).max(1)),
+                        KeyCode::Up => (dim.width, dim.height.saturating_add(1)),
+                        _ => unreachable!(),
+                    };
+                    if width.checked_mul(height).is_some() {
+                        world.resize(width, height);
+                        // edits refer to tiles by offsets of the old size
+                        history = History::default();
+                        view.cursor = (view.cursor.0.min(width - 1), view.cursor.1.min(height - 1));
+                        // the traced tile may be cut off
+                        view.trace = None;
+                        message = Some(format!("resized to {width}x{height}"));
+                    }
+                }
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('D'),
+                    ..
+                }) => world.diagonal = !world.diagonal,
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('o'),
+                    ..
+                }) => {
+                    let cursor = view.cursor;
+                    let tile = &mut world[cursor];
+                    if tile.ty == TileType::Diode {
+                        // rotate clockwise: > v < ^
+                        tile.facing = [3, 2, 0, 1][tile.facing];
+                        // flag the diode to push its signal the new way
+                        world.set_type(cursor, TileType::Diode);
+                    }
+                }
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char(ch @ ('[' | ']')),
+                    ..
+                }) => {
+                    let cursor = view.cursor;
+                    let tile = &mut world[cursor];
+                    match tile.ty {
+                        TileType::Source => {
+                            let strength = &mut tile.strength.0;
+                            *strength = match ch {
+                                '[' => strength.saturating_sub(STRENGTH_STEP),
+                                ']' => strength.saturating_add(STRENGTH_STEP),
+                                _ => unreachable!(),
+                            };
+                        }
+                        // a stronger conductor resists less
+                        TileType::Conductor => {
+                            let resistance = &mut tile.resistance;
+                            *resistance = match ch {
+                                '[' => (*resistance + RESISTANCE_STEP).min(100),
+                                ']' => resistance.saturating_sub(RESISTANCE_STEP),
+                                _ => unreachable!(),
+                            };
+                        }
+                        _ => {}
+                    }
+                }
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char(ch @ ('(' | ')')),
+                    ..
+                }) => {
+                    let weight = world[view.cursor].weight(&world.rules);
+                    let tile = &mut world[view.cursor];
+                    if weight.0 > 0 {
+                        tile.fuel = match (ch, tile.fuel) {
+                            ('(', None) => Some(MAX_FUEL),
+                            ('(', Some(fuel)) => Some(fuel.saturating_sub(FUEL_STEP).max(1)),
+                            (')', Some(fuel)) if fuel < MAX_FUEL => {
+                                Some((fuel + FUEL_STEP).min(MAX_FUEL))
+                            }
+                            (')', _) => None,
+                            _ => unreachable!(),
+                        };
+                    }
+                }
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('m'),
+                    ..
+                }) => {
+                    view.set_colormap(view.colormap.next());
+                    message = Some(format!("colormap: {}", view.colormap.name()));
+                }
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('M'),
+                    ..
+                }) => {
+                    view.channels = !view.channels;
+                    message = Some(
+                        if view.channels {
+                            "mixing channels as red, green and blue"
+                        } else {
+                            "coloring by the colormap"
+                        }
+                        .to_string(),
+                    );
+                }
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('I'),
+                    ..
+                }) => {
+                    view.smooth = !view.smooth;
+                    // interpolation starts with the next tick
+                    view.remember_signals(&world);
+                    message = Some(
+                        if view.smooth {
+                            "interpolating between ticks"
+                        } else {
+                            "drawing every tick as is"
+                        }
+                        .to_string(),
+                    );
+                }
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('z'),
+                    ..
+                }) => {
+                    // a count retraces with that limit, otherwise z toggles
+                    view.trace = match (view.trace, pending_count) {
+                        (Some(_), None) => None,
+                        (_, count) => Some((view.cursor, count.unwrap_or(DEFAULT_TRACE_HOPS))),
+                    };
+                }
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('R'),
+                    ..
+                }) => world.random_ticks_enabled = !world.random_ticks_enabled,
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('V'),
+                    ..
+                }) => {
+                    view.labels = match view.labels {
+                        Some(_) => None,
+                        None => Some(args.label_stride),
+                    };
+                }
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('H'),
+                    ..
+                }) => view.heightmap = !view.heightmap,
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('F'),
+                    ..
+                }) => view.show_flagged = !view.show_flagged,
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('Z'),
+                    ..
+                }) => pen_down = !pen_down,
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('U'),
+                    ..
+                }) => view.flip_y = !view.flip_y,
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('O'),
+                    ..
+                }) => {
+                    view.activity = !view.activity;
+                    message = Some(
+                        if view.activity {
+                            "coloring by the ticks since last flagged"
+                        } else {
+                            "coloring by signal"
+                        }
+                        .to_string(),
+                    );
+                }
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('n'),
+                    ..
+                }) => {
+                    let tile = &mut world[view.cursor];
+                    if tile.ty == TileType::Source {
+                        tile.channel = (tile.channel + 1) % CHANNELS;
+                    }
+                }
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('N'),
+                    ..
+                }) => {
+                    // cycles from the frame maximum to the running maximum to the ceiling
+                    (view.running_max, view.against_ceiling) =
+                        match (view.running_max, view.against_ceiling) {
+                            (_, true) => (None, false),
+                            (Some(_), false) => (None, true),
+                            (None, false) => (Some(f64::from(world.max_signal_sum().0)), false),
+                        };
+                    message = Some(
+                        match (view.running_max, view.against_ceiling) {
+                            (_, true) => "normalizing against the signal ceiling",
+                            (Some(_), _) => "normalizing against the running maximum",
+                            (None, _) => "normalizing against the frame maximum",
+                        }
+                        .to_string(),
+                    );
+                }
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('i'),
+                    ..
+                }) => overlay = Some(Overlay::Inspect),
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('K'),
+                    ..
+                }) => {
+                    let tile = &mut world[view.cursor];
+                    tile.locked = !tile.locked;
+                }
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('P'),
+                    ..
+                }) => {
+                    let probed = world.toggle_probe(view.cursor);
+                    message = Some(
+                        if probed {
+                            "probing the cursor tile"
+                        } else {
+                            "stopped probing the cursor tile"
+                        }
+                        .to_string(),
+                    );
+                }
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('?'),
+                    ..
+                }) => overlay = Some(Overlay::Help { page: 0 }),
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('A'),
+                    ..
+                }) => {
+                    let empty = world.tiles().iter().all(|tile| tile.ty == TileType::Air);
+                    if confirming_fill || empty {
+                        let dim = world.dim();
+                        let tiles: Vec<_> = (0..dim.height)
+                            .flat_map(|y| (0..dim.width).map(move |x| (x, y)))
+                            .collect();
+                        let skipped = history.paint(&mut world, tiles, brush);
+                        // start from a uniform state as if the world was built this way
+                        world.clear_signals();
+                        message = Some(format!("filled the world with {brush:?}"));
+                        if let Some(skipped) = skipped_locked(skipped) {
+                            message = Some(skipped);
+                        }
+                    } else {
+                        fill_requested = true;
+                        message = Some(format!(
+                            "press A again to overwrite the world with {brush:?}"
+                        ));
+                    }
+                }
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('c'),
+                    ..
+                }) => {
+                    if confirming_clear {
+                        world.clear();
+                        if args.floor {
+                            lay_floor(&mut world);
+                        }
+                        history = History::default();
+                        message = Some("cleared".to_string());
+                    } else {
+                        clear_requested = true;
+                        message = Some("press c again to clear the world".to_string());
+                    }
+                }
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('C'),
+                    ..
+                }) => {
+                    world.clear_signals();
+                    message = Some("cleared signals".to_string());
+                }
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('t'),
+                    ..
+                }) => step_requested = true,
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('T'),
+                    ..
+                }) => step_prompt = Some(String::new()),
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('w'),
+                    ..
+                }) => {
+                    let result = fs::File::create(&args.save_path).and_then(|file| {
+                        let mut w = io::BufWriter::new(file);
+                        world.save(&mut w)?;
+                        w.flush()
+                    });
+                    message = Some(match result {
+                        Ok(()) => format!("saved to {}", args.save_path),
+                        Err(err) => format!("cannot save to {}: {err}", args.save_path),
+                    });
+                }
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('J'),
+                    ..
+                }) => {
+                    let path = &args.json_export_path;
+                    let result = fs::File::create(path).and_then(|file| {
+                        let mut w = io::BufWriter::new(file);
+                        world.export_json(&mut w)?;
+                        w.flush()
+                    });
+                    message = Some(match result {
+                        Ok(()) => format!("exported the tiles to {path}"),
+                        Err(err) => format!("cannot export the tiles to {path}: {err}"),
+                    });
+                }
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('Y'),
+                    ..
+                }) => {
+                    let path = &args.screenshot_path;
+                    let ansi = !path.ends_with(".txt");
+                    let result = fs::File::create(path)
+                        .and_then(|file| screen.dump(io::BufWriter::new(file), ansi));
+                    message = Some(match result {
+                        Ok(()) => format!("saved the screen to {path}"),
+                        Err(err) => format!("cannot save the screen to {path}: {err}"),
+                    });
+                }
+                #[cfg(feature = "snapshot")]
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('W'),
+                    ..
+                }) => {
+                    let snapshot = Snapshot {
+                        world: &world,
+                        cursor: view.cursor,
+                        current_tick,
+                    };
+                    message = Some(match snapshot.save(&args.snapshot_path) {
+                        Ok(()) => format!("saved a snapshot to {}", args.snapshot_path),
+                        Err(err) => {
+                            format!("cannot save a snapshot to {}: {err}", args.snapshot_path)
+                        }
+                    });
+                }
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('p'),
+                    ..
+                }) => {
+                    message = Some(match export(&world, &args.export_path) {
+                        Ok(()) => format!("exported to {}", args.export_path),
+                        Err(err) => format!("cannot export to {}: {err}", args.export_path),
+                    });
+                }
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char(' '),
+                    ..
+                }) => paused = !paused,
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char(ch @ ('+' | '-')),
+                    ..
+                }) => {
+                    tick_freq = match ch {
+                        '+' => (tick_freq / 2).max(MIN_TICK_FREQ),
+                        '-' => (tick_freq * 2).min(MAX_TICK_FREQ),
+                        _ => unreachable!(),
+                    };
+                    // reschedule the pending tick relative to the last one,
+                    // otherwise a shorter interval would wait out the old one
+                    next_tick_time = last_tick_time + tick_freq;
+                }
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('B'),
+                    ..
+                }) => world.burst(view.cursor, burst),
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char(ch @ (',' | '.')),
+                    ..
+                }) => {
+                    burst.0 = match ch {
+                        ',' => burst.0.saturating_sub(BURST_STEP).max(BURST_STEP),
+                        '.' => burst.0.saturating_add(BURST_STEP),
+                        _ => unreachable!(),
+                    };
+                }
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char(ch @ ('{' | '}')),
+                    ..
+                }) => {
+                    // stepping in tenths, rounded so that the steps do not drift
+                    let tenths = (world.flag_rate * 10.0).round();
+                    let tenths = match ch {
+                        '{' => (tenths - 1.0).max(0.0),
+                        '}' => (tenths + 1.0).min(10.0),
+                        _ => unreachable!(),
+                    };
+                    world.flag_rate = tenths / 10.0;
+                }
+                Event::Mouse(MouseEvent {
+                    kind: kind @ (MouseEventKind::Down(button) | MouseEventKind::Drag(button)),
+                    column,
+                    row,
+                    ..
+                }) => {
+                    let ty = match button {
+                        MouseButton::Left => Some(brush),
+                        MouseButton::Right => Some(TileType::Air),
+                        MouseButton::Middle => None,
+                    };
+                    if let (Some(ty), Some(xy)) = (ty, view.grid_xy(column, row)) {
+                        // drag events skip cells when the pointer moves fast,
+                        // so connect them to the previous position
+                        let centers = match kind {
+                            MouseEventKind::Drag(_) => line(view.cursor, xy),
+                            _ => vec![xy],
+                        };
+                        let tiles: Vec<_> = centers
+                            .into_iter()
+                            .flat_map(|center| brush_area(world.dim(), center, brush_radius))
+                            .collect();
+                        view.cursor = xy;
+                        let skipped = history.paint(&mut world, tiles, ty);
+                        if let Some(skipped) = skipped_locked(skipped) {
+                            message = Some(skipped);
+                        }
+                    }
+                }
+                // the viewport is refitted to the new size on the next frame,
+                // but some terminals scramble the existing output while resizing
+                Event::Resize(..) => screen.invalidate(),
+                _ => {}
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A view on a terminal just large enough to show all of a `width` by `height` world.
+    fn fitted_view(width: usize, height: usize) -> View {
+        let dim = Dim { width, height };
+        let (cols, rows) = Viewport::term_size_for(width, height);
+        let mut view = View::default();
+        view.fit_viewport(&dim, (cols as u16, rows as u16));
+        view
+    }
+
+    #[test]
+    fn term_positions_round_trip_for_odd_sizes() {
+        for (width, height) in [(1, 1), (3, 5), (7, 9), (11, 3)] {
+            let view = fitted_view(width, height);
+            assert_eq!((view.viewport.width, view.viewport.height), (width, height));
+            for x in 0..width {
+                for y in 0..height {
+                    let (column, row) = (view.term_x(x), view.term_y(y));
+                    assert_eq!(view.grid_xy(column, row), Some((x, y)));
+                    // the column right of the glyph belongs to the same tile
+                    assert_eq!(view.grid_xy(column + 1, row), Some((x, y)));
+                }
+            }
+            assert_eq!(usize::from(view.term_y(0)), PADDING_TOP + height);
+            assert_eq!(usize::from(view.term_y(height - 1)), PADDING_TOP + 1);
+        }
+    }
+
+    #[test]
+    fn labels_follow_the_y_axis_in_both_orientations() {
+        for flip_y in [false, true] {
+            let mut view = fitted_view(5, 7);
+            view.flip_y = flip_y;
+            // the y-axis labels are printed in the rows of their y
+            let top = PADDING_TOP as u16 + 1;
+            let bottom = (PADDING_TOP + 7) as u16;
+            let (top_label, bottom_label) = if flip_y { (0, 6) } else { (6, 0) };
+            assert_eq!(view.term_y(top_label), top, "flip_y: {flip_y}");
+            assert_eq!(view.term_y(bottom_label), bottom, "flip_y: {flip_y}");
+
+            // the legend rises from nothing at the bottom row towards the maximum at the top
+            let mut rows: Vec<_> = (0..7)
+                .map(|y| (view.term_y(y), view.legend_ratio(y)))
+                .collect();
+            rows.sort_by_key(|&(row, _)| row);
+            assert_eq!(rows.last().unwrap().1, 0.0, "flip_y: {flip_y}");
+            assert!(
+                rows.windows(2).all(|pair| pair[0].1 > pair[1].1),
+                "flip_y: {flip_y}"
+            );
+        }
+    }
+
+    #[test]
+    fn help_pages_fit_the_terminal() {
+        for term_size in [(80, 24), (150, 20), (240, 25), (400, 100), (10, 4)] {
+            let (rows, columns) = help_page_size(term_size);
+            let pages = help_pages(term_size);
+            assert!(rows * columns * pages >= KEYBINDINGS.len());
+            assert!(rows * columns * (pages - 1) < KEYBINDINGS.len());
+            // the footer goes below a blank row after the list
+            assert!(rows + 2 < usize::from(term_size.1), "{term_size:?}");
+            if columns > 1 {
+                let width = 2 + columns * help_entry_width() + (columns - 1) * HELP_COLUMN_GAP;
+                assert!(width <= usize::from(term_size.0), "{term_size:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn undo_and_redo_skip_locked_tiles() {
+        let mut world = World::new(3, 1, 0);
+        let mut history = History::default();
+        history.paint(&mut world, (0..3).map(|x| (x, 0)), TileType::Brick);
+        world[(1, 0)].locked = true;
+
+        assert!(history.undo(&mut world));
+        let types = |world: &World| world.tiles().iter().map(|tile| tile.ty).collect::<Vec<_>>();
+        assert_eq!(
+            types(&world),
+            [TileType::Air, TileType::Brick, TileType::Air]
+        );
+
+        world[(1, 0)].locked = false;
+        world[(0, 0)].locked = true;
+        assert!(history.redo(&mut world));
+        assert_eq!(
+            types(&world),
+            [TileType::Air, TileType::Brick, TileType::Brick]
+        );
+    }
+
+    #[test]
+    fn palette_matches_shading_directly() {
+        let mut view = View::default();
+        for colormap in Colormap::ALL {
+            view.set_colormap(colormap);
+            let gradient = colormap.gradient();
+            for level in 0..=1000 {
+                let ratio = level as f64 / 1000.0;
+                let (
+                    Color::Rgb { r, g, b },
+                    Color::Rgb {
+                        r: r2,
+                        g: g2,
+                        b: b2,
+                    },
+                ) = (view.color(ratio), shade(&gradient, ratio))
+                else {
+                    panic!("{colormap:?} is not shaded in rgb");
+                };
+                let error = [(r, r2), (g, g2), (b, b2)]
+                    .map(|(sampled, direct)| sampled.abs_diff(direct))
+                    .into_iter()
+                    .max()
+                    .unwrap();
+                // the steep ends of turbo change by up to 3 per palette level
+                assert!(error <= 3, "{colormap:?} at {ratio} is off by {error}");
+            }
+        }
+    }
+
+    #[test]
+    fn cursor_moves_the_way_it_is_drawn() {
+        let dim = Dim {
+            width: 3,
+            height: 5,
+        };
+        for flip_y in [false, true] {
+            let mut view = fitted_view(dim.width, dim.height);
+            view.flip_y = flip_y;
+            view.cursor = (1, 2);
+            let screen = |view: &View| (view.term_x(view.cursor.0), view.term_y(view.cursor.1));
+            let (column, row) = screen(&view);
+
+            // `k` moves up on screen and `j` down, whichever way y grows
+            view.move_cursor(&dim, (0, 1), 1);
+            assert_eq!(screen(&view), (column, row - 1), "flip_y: {flip_y}");
+            view.move_cursor(&dim, (0, -1), 2);
+            assert_eq!(screen(&view), (column, row + 1), "flip_y: {flip_y}");
+            view.move_cursor(&dim, (1, 0), 1);
+            assert_eq!(screen(&view), (column + 2, row + 1), "flip_y: {flip_y}");
+
+            // counts stop at the edges of the grid
+            view.move_cursor(&dim, (1, 1), usize::MAX);
+            assert_eq!(screen(&view), (view.term_x(2), PADDING_TOP as u16 + 1));
+            view.move_cursor(&dim, (-1, -1), usize::MAX);
+            assert_eq!(
+                screen(&view),
+                (PADDING_LEFT as u16, (PADDING_TOP + dim.height) as u16)
+            );
+        }
+    }
 }
@@ -0,0 +1,93 @@
+use anyhow::{Context, Result};
+use crossterm::event::Event;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// An input event along with the tick that ran next after it.
+#[derive(Serialize, Deserialize)]
+struct Input {
+    tick: u32,
+    event: Event,
+}
+
+/// The TOML layout of an input log.
+#[derive(Serialize, Deserialize)]
+struct InputLog {
+    /// The seed of the recorded session, which replays need to match it.
+    seed: u64,
+    #[serde(default)]
+    inputs: Vec<Input>,
+}
+
+/// Appends every handled input event to a file as it happens,
+/// so that the log survives a panic.
+pub struct InputRecorder {
+    pub path: PathBuf,
+    w: io::BufWriter<fs::File>,
+}
+
+impl InputRecorder {
+    pub fn new(path: PathBuf, seed: u64) -> io::Result<Self> {
+        let mut w = io::BufWriter::new(fs::File::create(&path)?);
+        writeln!(w, "seed = {seed}")?;
+        w.flush()?;
+        Ok(Self { path, w })
+    }
+
+    pub fn record(&mut self, tick: u32, event: &Event) -> io::Result<()> {
+        /// A single element of [`InputLog::inputs`], appended as an array of tables.
+        #[derive(Serialize)]
+        struct Appended<'a> {
+            inputs: [&'a Input; 1],
+        }
+
+        let input = Input {
+            tick,
+            event: event.clone(),
+        };
+        let chunk = toml::to_string(&Appended { inputs: [&input] }).map_err(io::Error::other)?;
+        writeln!(self.w)?;
+        self.w.write_all(chunk.as_bytes())?;
+        self.w.flush()
+    }
+}
+
+/// The events of an input log, fed back to the main loop in their recorded order.
+pub struct Replay {
+    pub seed: u64,
+    inputs: VecDeque<Input>,
+}
+
+impl Replay {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text =
+            fs::read_to_string(path).with_context(|| format!("cannot read {}", path.display()))?;
+        let log: InputLog =
+            toml::from_str(&text).with_context(|| format!("cannot parse {}", path.display()))?;
+        Ok(Self {
+            seed: log.seed,
+            inputs: log.inputs.into(),
+        })
+    }
+
+    /// Whether an event recorded before `now` ran is yet to be replayed,
+    /// in which case the tick has to wait for it.
+    pub fn is_due(&self, now: u32) -> bool {
+        self.inputs.front().is_some_and(|input| input.tick <= now)
+    }
+
+    pub fn next_due(&mut self, now: u32) -> Option<Event> {
+        if self.is_due(now) {
+            self.inputs.pop_front().map(|input| input.event)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.inputs.is_empty()
+    }
+}
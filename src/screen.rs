@@ -0,0 +1,180 @@
+use crossterm::event::{DisableMouseCapture, EnableMouseCapture};
+use crossterm::style::{self, Color};
+use crossterm::{cursor, terminal, ExecutableCommand, QueueableCommand};
+use std::fmt::{self, Write as _};
+use std::io::{self, Write};
+use std::panic;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Cell {
+    ch: char,
+    fg: Color,
+    bg: Color,
+}
+
+const BLANK: Cell = Cell {
+    ch: ' ',
+    fg: Color::Reset,
+    bg: Color::Reset,
+};
+
+/// An in-memory frame of the terminal,
+/// flushed by only writing the cells that changed since the last flush.
+#[derive(Default)]
+pub struct Screen {
+    cols: u16,
+    rows: u16,
+    cells: Vec<Cell>,
+    /// The cells as last flushed to the terminal,
+    /// or empty if the terminal has to be redrawn from scratch.
+    flushed: Vec<Cell>,
+    text: String,
+}
+
+impl Screen {
+    /// Starts a blank frame of `cols` by `rows`.
+    pub fn begin(&mut self, (cols, rows): (u16, u16)) {
+        if (cols, rows) != (self.cols, self.rows) {
+            self.cols = cols;
+            self.rows = rows;
+            self.invalidate();
+        }
+        self.cells.clear();
+        self.cells
+            .resize(usize::from(cols) * usize::from(rows), BLANK);
+    }
+
+    /// Makes the next flush redraw the whole terminal.
+    pub fn invalidate(&mut self) {
+        self.flushed.clear();
+    }
+
+    pub fn print(&mut self, x: u16, y: u16, text: impl fmt::Display) {
+        self.print_styled(x, y, text, Color::Reset, Color::Reset);
+    }
+
+    /// Writes `text` starting at `(x, y)`, clipped to the end of the row.
+    pub fn print_styled(&mut self, x: u16, y: u16, text: impl fmt::Display, fg: Color, bg: Color) {
+        if y >= self.rows {
+            return;
+        }
+        self.text.clear();
+        write!(self.text, "{text}").unwrap();
+
+        let row_start = usize::from(y) * usize::from(self.cols);
+        for (col, ch) in (x..self.cols).zip(self.text.chars()) {
+            self.cells[row_start + usize::from(col)] = Cell { ch, fg, bg };
+        }
+    }
+
+    /// Writes the changed cells to the terminal in a single write
+    /// and leaves the terminal cursor at `cursor`.
+    pub fn flush(&mut self, cursor: (u16, u16)) -> io::Result<()> {
+        let mut out = Vec::new();
+        let redraw = self.flushed.len() != self.cells.len();
+        if redraw {
+            out.queue(terminal::Clear(terminal::ClearType::All))?;
+        }
+
+        let cols = usize::from(self.cols);
+        // where the terminal cursor is after the last printed cell, and the colors in effect
+        let mut next_pos = None;
+        let mut colors = None;
+        for (i, &cell) in self.cells.iter().enumerate() {
+            let unchanged = if redraw {
+                cell == BLANK
+            } else {
+                self.flushed[i] == cell
+            };
+            if unchanged {
+                continue;
+            }
+
+            let pos = ((i % cols) as u16, (i / cols) as u16);
+            if next_pos != Some(pos) {
+                out.queue(cursor::MoveTo(pos.0, pos.1))?;
+            }
+            if colors != Some((cell.fg, cell.bg)) {
+                out.queue(style::SetForegroundColor(cell.fg))?
+                    .queue(style::SetBackgroundColor(cell.bg))?;
+                colors = Some((cell.fg, cell.bg));
+            }
+            out.queue(style::Print(cell.ch))?;
+            // the terminal does not wrap to the next row predictably
+            next_pos = (usize::from(pos.0) + 1 < cols).then_some((pos.0 + 1, pos.1));
+        }
+
+        out.queue(style::ResetColor)?
+            .queue(cursor::MoveTo(cursor.0, cursor.1))?;
+
+        let mut stdout = io::stdout();
+        stdout.write_all(&out)?;
+        stdout.flush()?;
+
+        self.flushed.clone_from(&self.cells);
+        Ok(())
+    }
+
+    /// Writes the last frame as text with one line per row and trailing blanks trimmed,
+    /// with ANSI color codes if `ansi` is set.
+    pub fn dump(&self, mut w: impl Write, ansi: bool) -> io::Result<()> {
+        let cols = usize::from(self.cols).max(1);
+        for row in self.cells.chunks(cols) {
+            let len = row
+                .iter()
+                .rposition(|&cell| cell != BLANK)
+                .map_or(0, |i| i + 1);
+            let mut colors = None;
+            for cell in &row[..len] {
+                if ansi && colors != Some((cell.fg, cell.bg)) {
+                    w.queue(style::SetForegroundColor(cell.fg))?
+                        .queue(style::SetBackgroundColor(cell.bg))?;
+                    colors = Some((cell.fg, cell.bg));
+                }
+                write!(w, "{}", cell.ch)?;
+            }
+            if ansi && colors.is_some() {
+                w.queue(style::ResetColor)?;
+            }
+            writeln!(w)?;
+        }
+        w.flush()
+    }
+}
+
+/// Keeps the terminal in raw mode on the alternate screen with mouse capture,
+/// restoring it when dropped or when the program panics.
+pub struct TerminalGuard(());
+
+impl TerminalGuard {
+    pub fn enter() -> io::Result<Self> {
+        // restore before the default hook prints the panic message,
+        // which would otherwise be lost on the alternate screen
+        let default_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            restore();
+            default_hook(info);
+        }));
+
+        terminal::enable_raw_mode()?;
+        io::stdout()
+            .execute(terminal::EnterAlternateScreen)?
+            .execute(EnableMouseCapture)?;
+        Ok(Self(()))
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore();
+    }
+}
+
+/// Leaves raw mode and the alternate screen, ignoring errors
+/// since this also runs while panicking.
+fn restore() {
+    _ = terminal::disable_raw_mode();
+    _ = io::stdout()
+        .execute(DisableMouseCapture)
+        .and_then(|stdout| stdout.execute(terminal::LeaveAlternateScreen));
+}
@@ -0,0 +1,1749 @@
+use arrayvec::ArrayVec;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use std::collections::VecDeque;
+use std::{iter, mem, ops};
+
+pub const DEFAULT_SIGNAL_BACKLOG_LENGTH: usize = 4;
+pub const DEFAULT_SIGNAL_BACKLOG_UNIT: Tick = Tick(8);
+/// The capacity of the backlog of each tile, bounding [`World::set_backlog`].
+pub const MAX_SIGNAL_BACKLOG_LENGTH: usize = 64;
+pub const DEFAULT_FLAG_RATE: f64 = 0.8;
+pub const DEFAULT_RANDOM_TICK_PERCENTAGE: usize = 20;
+/// Opposite sides are adjacent, i.e. `side ^ 1` is the reverse of `side`.
+pub const SIDES: [(isize, isize); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+/// [`SIDES`] followed by the diagonal neighbors, keeping opposite sides adjacent.
+const SIDES_DIAGONAL: [(isize, isize); 8] = [
+    (1, 0),
+    (-1, 0),
+    (0, 1),
+    (0, -1),
+    (1, 1),
+    (-1, -1),
+    (1, -1),
+    (-1, 1),
+];
+/// Rendered diodes indexed by the side in [`SIDES`] they face.
+const DIODE_GLYPHS: [char; 4] = ['>', '<', '^', 'v'];
+/// The strength of newly placed sources, matching the weight of brick.
+pub const DEFAULT_SOURCE_STRENGTH: Signal = Signal(100);
+/// The resistance of newly placed conductors.
+pub const DEFAULT_RESISTANCE: u8 = 10;
+/// The default fraction of `next_signal` a sink removes every tick.
+pub const DEFAULT_SINK_DRAIN: f64 = 0.1;
+/// The fraction of `next_signal` air loses every tick,
+/// so that no stale signal lingers where emitters used to be.
+const AIR_DRAIN: f64 = 0.5;
+/// With the `rayon` feature, [`World::pre_tick`] scans every tile in parallel
+/// once at least 1 in this many tiles is dirty.
+#[cfg(feature = "rayon")]
+const PARALLEL_ROLL_SHARE: usize = 4;
+/// The number of independent signals every tile carries.
+/// Brick and water emit into channel 0 and sources into [`Tile::channel`].
+pub const CHANNELS: usize = 3;
+/// The number of ticks each [`Probe`] remembers.
+pub const PROBE_SAMPLES: usize = 60;
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "snapshot", derive(serde::Serialize, serde::Deserialize))]
+pub struct Tick(pub u32);
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "snapshot", derive(serde::Serialize, serde::Deserialize))]
+pub struct Signal(pub u16);
+
+impl Signal {
+    /// Normalizes this signal against `max` into `0.0..=1.0`.
+    /// An all-zero field normalizes to 0 instead of NaN.
+    pub fn ratio(self, max: Signal) -> f64 {
+        if max.0 == 0 {
+            0.0
+        } else {
+            self.0 as f64 / max.0 as f64
+        }
+    }
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "snapshot", derive(serde::Serialize, serde::Deserialize))]
+pub struct Tile {
+    pub ty: TileType,
+    signals: ArrayVec<[Signal; CHANNELS], MAX_SIGNAL_BACKLOG_LENGTH>,
+    signal_sums: [Signal; CHANNELS],
+
+    next_signals: [Signal; CHANNELS],
+
+    /// The index in [`SIDES`] a diode propagates towards.
+    pub facing: usize,
+
+    /// The signal a source injects on random ticks.
+    pub strength: Signal,
+    /// The channel a source injects into.
+    pub channel: usize,
+    /// The percentage of signal, in `0..=100`, a conductor loses whenever it propagates.
+    pub resistance: u8,
+    /// The number of random ticks an emitter still injects signal on
+    /// before it burns out to air, or `None` to emit forever.
+    pub fuel: Option<u32>,
+    /// Whether the tile is protected from being overwritten by edits and kept by [`World::clear`].
+    pub locked: bool,
+    /// The last tick in which the tile was flagged, or `None` if it never was.
+    pub last_active_tick: Option<Tick>,
+
+    /// Whether the tile is queued in [`World::dirty_tiles`].
+    dirty: bool,
+}
+
+/// Sums the channels of a signal, saturating like each channel does.
+fn total(signals: [Signal; CHANNELS]) -> Signal {
+    Signal(
+        signals
+            .iter()
+            .fold(0u16, |sum, signal| sum.saturating_add(signal.0)),
+    )
+}
+
+impl Tile {
+    /// A signal-free air tile with `backlog_length` backlog slots.
+    pub fn new(backlog_length: usize) -> Self {
+        Self {
+            ty: TileType::Air,
+            signals: iter::repeat_n([Signal(0); CHANNELS], backlog_length).collect(),
+            signal_sums: [Signal(0); CHANNELS],
+            next_signals: [Signal(0); CHANNELS],
+            facing: 0,
+            strength: DEFAULT_SOURCE_STRENGTH,
+            channel: 0,
+            resistance: DEFAULT_RESISTANCE,
+            fuel: None,
+            locked: false,
+            last_active_tick: None,
+            dirty: false,
+        }
+    }
+
+    /// The same tile with all of its signal reset.
+    fn without_signal(&self, backlog_length: usize) -> Self {
+        Self {
+            ty: self.ty,
+            facing: self.facing,
+            strength: self.strength,
+            channel: self.channel,
+            resistance: self.resistance,
+            fuel: self.fuel,
+            locked: self.locked,
+            ..Self::new(backlog_length)
+        }
+    }
+
+    /// The signal of each channel received in each of the last
+    /// [`World::backlog_length`] backlog units.
+    pub fn signals(&self) -> &[[Signal; CHANNELS]] {
+        &self.signals
+    }
+
+    /// The sum of [`Tile::signals`] in each channel.
+    pub fn signal_sums(&self) -> [Signal; CHANNELS] {
+        self.signal_sums
+    }
+
+    /// The sum of [`Tile::signal_sums`] over all channels.
+    pub fn signal_sum(&self) -> Signal {
+        total(self.signal_sums)
+    }
+
+    /// The signal of each channel to be stored into the backlog in the next tick.
+    pub fn next_signals(&self) -> [Signal; CHANNELS] {
+        self.next_signals
+    }
+
+    /// The sum of [`Tile::next_signals`] over all channels.
+    pub fn next_signal(&self) -> Signal {
+        total(self.next_signals)
+    }
+
+    /// Scales `next_signals` by `decay`, and sinks and air further by their drain.
+    /// The result is rounded down so that decaying tiles eventually run dry.
+    /// Returns whether `next_signals` keep decaying in the next tick.
+    fn decay(&mut self, decay: f64, sink_drain: f64) -> bool {
+        let mut factor = decay;
+        match self.ty {
+            TileType::Sink => factor *= 1.0 - sink_drain,
+            TileType::Air => factor *= 1.0 - AIR_DRAIN,
+            _ => {}
+        }
+        if factor >= 1.0 {
+            return false;
+        }
+        for next_signal in &mut self.next_signals {
+            next_signal.0 = (f64::from(next_signal.0) * factor) as u16;
+        }
+        self.next_signals.iter().any(|signal| signal.0 > 0)
+    }
+
+    /// Decays `next_signals` and stores them into the backlog slot at `offset`,
+    /// keeping [`Tile::signal_sum`] within `ceiling`.
+    /// Returns whether rolling again would leave the tile unchanged.
+    fn roll_backlog(
+        &mut self,
+        offset: usize,
+        decay: f64,
+        sink_drain: f64,
+        ceiling: Signal,
+    ) -> bool {
+        let decaying = self.decay(decay, sink_drain);
+
+        // signal_sums is always exactly the sum of the backlog,
+        // so the expired slot can never exceed it.
+        let remaining: [u16; CHANNELS] = std::array::from_fn(|channel| {
+            let expired = self.signals[offset][channel].0;
+            debug_assert!(expired <= self.signal_sums[channel].0);
+            self.signal_sums[channel].0 - expired
+        });
+        // earlier channels take up the room below the ceiling first
+        let mut room = ceiling.0.saturating_sub(total(remaining.map(Signal)).0);
+
+        for (channel, remaining) in remaining.into_iter().enumerate() {
+            // Only the part of next_signal that fits is stored,
+            // such that the invariant above survives saturation.
+            let added = self.next_signals[channel]
+                .0
+                .min(u16::MAX - remaining)
+                .min(room);
+            room -= added;
+            self.signals[offset][channel] = Signal(added);
+            self.signal_sums[channel].0 = remaining + added;
+        }
+
+        !decaying
+            && self.signals.iter().all(|slot| {
+                slot.iter()
+                    .zip(self.next_signals)
+                    .all(|(signal, next_signal)| signal.0 == next_signal.0)
+            })
+    }
+
+    /// The signal injected on random ticks, which is per-tile for sources.
+    pub fn weight(&self, rules: &Rules) -> Signal {
+        match self.ty {
+            TileType::Source => self.strength,
+            ty => ty.weight(rules),
+        }
+    }
+
+    /// The channel that random ticks inject [`Tile::weight`] into.
+    pub fn emitted_channel(&self) -> usize {
+        match self.ty {
+            TileType::Source => self.channel,
+            _ => 0,
+        }
+    }
+
+    pub fn rendered(&self) -> char {
+        match self.ty {
+            TileType::Diode => DIODE_GLYPHS[self.facing],
+            ty => ty.rendered(),
+        }
+    }
+
+    pub fn from_rendered(ch: char, backlog_length: usize) -> Option<Self> {
+        let mut tile = Self::new(backlog_length);
+        if let Some(facing) = DIODE_GLYPHS.iter().position(|&glyph| glyph == ch) {
+            tile.ty = TileType::Diode;
+            tile.facing = facing;
+        } else {
+            tile.ty = TileType::from_rendered(ch)?;
+        }
+        Some(tile)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "snapshot", derive(serde::Serialize, serde::Deserialize))]
+pub enum TileType {
+    Air,
+    Bedrock,
+    Brick,
+    Water,
+    Diode,
+    Source,
+    Sink,
+    Conductor,
+    /// Like air, signal never enters it, but it is placed deliberately as a barrier.
+    Wall,
+}
+
+impl TileType {
+    /// Every tile type, in declaration order.
+    pub const ALL: [Self; 9] = [
+        Self::Air,
+        Self::Bedrock,
+        Self::Brick,
+        Self::Water,
+        Self::Diode,
+        Self::Source,
+        Self::Sink,
+        Self::Conductor,
+        Self::Wall,
+    ];
+
+    pub fn rendered(self) -> char {
+        match self {
+            Self::Air => ' ',
+            Self::Bedrock => '=',
+            Self::Brick => 'o',
+            Self::Water => '~',
+            Self::Diode => DIODE_GLYPHS[0],
+            Self::Source => '*',
+            Self::Sink => '_',
+            Self::Conductor => '#',
+            Self::Wall => 'X',
+        }
+    }
+
+    pub fn from_rendered(ch: char) -> Option<Self> {
+        match ch {
+            ' ' => Some(Self::Air),
+            '=' => Some(Self::Bedrock),
+            'o' => Some(Self::Brick),
+            '~' => Some(Self::Water),
+            '*' => Some(Self::Source),
+            '_' => Some(Self::Sink),
+            '#' => Some(Self::Conductor),
+            'X' => Some(Self::Wall),
+            _ => None,
+        }
+    }
+
+    pub fn weight(self, rules: &Rules) -> Signal {
+        rules[self].weight
+    }
+
+    /// The built-in behavior of the type, used by [`Rules::default`].
+    fn default_rules(self) -> TileRules {
+        TileRules {
+            accepts: matches!(
+                self,
+                Self::Bedrock
+                    | Self::Brick
+                    | Self::Water
+                    | Self::Diode
+                    | Self::Source
+                    | Self::Sink
+                    | Self::Conductor
+            ),
+            emits: matches!(
+                self,
+                Self::Brick
+                    | Self::Water
+                    | Self::Diode
+                    | Self::Source
+                    | Self::Sink
+                    | Self::Conductor
+            ),
+            absorbs: matches!(self, Self::Bedrock),
+            weight: self.default_weight(),
+        }
+    }
+
+    fn default_weight(self) -> Signal {
+        match self {
+            Self::Air => Signal(0),
+            Self::Bedrock => Signal(0),
+            Self::Brick => Signal(100),
+            Self::Water => Signal(100),
+            Self::Diode => Signal(0),
+            Self::Source => DEFAULT_SOURCE_STRENGTH,
+            Self::Sink => Signal(0),
+            Self::Conductor => Signal(0),
+            Self::Wall => Signal(0),
+        }
+    }
+
+    /// The share of emitted signal pushed towards the neighbor at offset `(dx, dy)`,
+    /// relative to the other accepting neighbors.
+    ///
+    /// Water flows downwards first and only then spreads sideways;
+    /// it never pushes signal upwards.
+    pub fn side_weight(self, (_dx, dy): (isize, isize)) -> u32 {
+        match self {
+            Self::Water => match dy {
+                ..=-1 => 2,
+                0 => 1,
+                1.. => 0,
+            },
+            _ => 1,
+        }
+    }
+
+    pub fn accepts(self, rules: &Rules) -> bool {
+        rules[self].accepts
+    }
+    pub fn emits(self, rules: &Rules) -> bool {
+        rules[self].emits
+    }
+    pub fn absorbs(self, rules: &Rules) -> bool {
+        rules[self].absorbs
+    }
+}
+
+/// How a tile type takes part in propagation.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "snapshot", derive(serde::Serialize, serde::Deserialize))]
+pub struct TileRules {
+    /// Whether emitting neighbors push signal into it.
+    pub accepts: bool,
+    /// Whether it passes its signal on to accepting neighbors when flagged.
+    pub emits: bool,
+    /// Whether signal pushed into it is discarded.
+    pub absorbs: bool,
+    /// The signal injected on random ticks, ignored for sources,
+    /// which use their own [`Tile::strength`].
+    pub weight: Signal,
+}
+
+/// The [`TileRules`] of every tile type.
+///
+/// The default reproduces the built-in behavior of each type.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "snapshot", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rules {
+    /// Indexed like [`TileType::ALL`].
+    tiles: [TileRules; TileType::ALL.len()],
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self {
+            tiles: TileType::ALL.map(TileType::default_rules),
+        }
+    }
+}
+
+impl ops::Index<TileType> for Rules {
+    type Output = TileRules;
+
+    fn index(&self, ty: TileType) -> &TileRules {
+        &self.tiles[ty as usize]
+    }
+}
+
+impl ops::IndexMut<TileType> for Rules {
+    fn index_mut(&mut self, ty: TileType) -> &mut TileRules {
+        &mut self.tiles[ty as usize]
+    }
+}
+
+/// A summary of the world, for judging whether it has reached a steady state.
+pub struct Stats {
+    /// The number of tiles of each type, indexed like [`TileType::ALL`].
+    pub counts: [usize; TileType::ALL.len()],
+    pub max_signal_sum: Signal,
+    /// The mean `signal_sum` over all tiles.
+    pub mean_signal: f64,
+    /// The number of tiles flagged for the next tick.
+    pub flagged: usize,
+}
+
+/// A tile whose `signal_sum` is sampled after every tick.
+#[cfg_attr(feature = "snapshot", derive(serde::Serialize, serde::Deserialize))]
+pub struct Probe {
+    pub xy: (usize, usize),
+    samples: VecDeque<Signal>,
+}
+
+impl Probe {
+    /// The last [`PROBE_SAMPLES`] values of `signal_sum`, oldest first.
+    pub fn samples(&self) -> &VecDeque<Signal> {
+        &self.samples
+    }
+}
+
+#[cfg_attr(feature = "snapshot", derive(serde::Serialize, serde::Deserialize))]
+pub struct Dim {
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Dim {
+    /// The row-major index of `(x, y)`, the inverse of [`Dim::offset_xy`].
+    ///
+    /// Panics if the coordinates are out of bounds
+    /// instead of aliasing a tile in another row.
+    pub fn xy_offset(&self, x: usize, y: usize) -> usize {
+        assert!(x < self.width);
+        assert!(y < self.height);
+        x + y * self.width
+    }
+
+    /// The coordinates at a row-major index, the inverse of [`Dim::xy_offset`].
+    ///
+    /// Panics if the offset is out of bounds.
+    pub fn offset_xy(&self, offset: usize) -> (usize, usize) {
+        assert!(offset < self.width * self.height);
+        let x = offset % self.width;
+        let y = offset / self.width;
+        (x, y)
+    }
+}
+
+#[cfg_attr(feature = "snapshot", derive(serde::Serialize, serde::Deserialize))]
+pub struct World {
+    dim: Dim,
+    tiles: Vec<Tile>,
+    flagged_tiles: Vec<usize>,
+    next_flagged_tiles: Vec<usize>,
+    /// Tiles whose backlog may still change in [`World::pre_tick`].
+    dirty_tiles: Vec<usize>,
+    /// The empty buffer [`World::pre_tick`] collects the next `dirty_tiles` into,
+    /// swapped with them so that neither is reallocated every tick.
+    spare_dirty_tiles: Vec<usize>,
+    backlog_length: usize,
+    backlog_unit: Tick,
+    probes: Vec<Probe>,
+    /// Free-form notes on the layout, such as its name or author,
+    /// kept as the `#` lines of [`World::save`].
+    pub comments: Vec<String>,
+    /// The generator behind `StdRng`, named directly so that its state can be snapshotted.
+    rng: ChaCha12Rng,
+    /// Whether signal also propagates to the four diagonal neighbors.
+    pub diagonal: bool,
+    /// Whether the grid edges wrap around like a torus.
+    pub wrap: bool,
+    /// The fraction of `next_signal` each sink removes every tick.
+    pub sink_drain: f64,
+    /// The factor every `next_signal` is scaled by each tick, in `0.0..=1.0`.
+    ///
+    /// Below 1, signal dissipates instead of accumulating in closed systems,
+    /// so [`World::total_signal`] is no longer conserved
+    /// but approaches a steady state where decay balances emission.
+    pub decay: f64,
+    /// The percentage of tiles receiving a [`World::random_tick`] every tick, in `0..=100`.
+    /// At 0, emitters never inject signal and only existing signal propagates.
+    pub random_tick_percentage: usize,
+    /// Whether random ticks run at all, pausing every emitter without changing
+    /// [`World::random_tick_percentage`] when unset.
+    pub random_ticks_enabled: bool,
+    /// The probability in `0.0..=1.0` that [`World::flagged_tick`] flags each neighbor it
+    /// propagates to, deciding how far the flagged frontier spreads in every tick.
+    ///
+    /// At 1, every accepting neighbor propagates in the next tick;
+    /// lower rates leave some neighbors holding their signal until reflagged,
+    /// and at 0 signal never travels further than one tile from a flagged emitter.
+    pub flag_rate: f64,
+    /// Whether every tile gets a [`World::random_tick`] and every neighbor is flagged
+    /// in every tick, overriding [`World::random_tick_percentage`] and [`World::flag_rate`]
+    /// so that ticking does not depend on the seed.
+    pub deterministic: bool,
+    /// The highest [`Tile::signal_sum`] any tile can hold,
+    /// beyond which incoming signal is discarded like in saturation.
+    pub signal_ceiling: Signal,
+    /// Which tile types accept, emit and absorb signal and how much they inject.
+    pub rules: Rules,
+}
+
+impl World {
+    pub fn new(width: usize, height: usize, seed: u64) -> Self {
+        Self {
+            dim: Dim { width, height },
+            tiles: iter::repeat_n(Tile::new(DEFAULT_SIGNAL_BACKLOG_LENGTH), width * height)
+                .collect(),
+            flagged_tiles: Vec::new(),
+            next_flagged_tiles: Vec::new(),
+            dirty_tiles: Vec::new(),
+            spare_dirty_tiles: Vec::new(),
+            backlog_length: DEFAULT_SIGNAL_BACKLOG_LENGTH,
+            backlog_unit: DEFAULT_SIGNAL_BACKLOG_UNIT,
+            probes: Vec::new(),
+            comments: Vec::new(),
+            rng: ChaCha12Rng::seed_from_u64(seed),
+            diagonal: false,
+            wrap: false,
+            sink_drain: DEFAULT_SINK_DRAIN,
+            decay: 1.0,
+            random_tick_percentage: DEFAULT_RANDOM_TICK_PERCENTAGE,
+            random_ticks_enabled: true,
+            flag_rate: DEFAULT_FLAG_RATE,
+            deterministic: false,
+            signal_ceiling: Signal(u16::MAX),
+            rules: Rules::default(),
+        }
+    }
+
+    pub fn dim(&self) -> &Dim {
+        &self.dim
+    }
+
+    pub fn tiles(&self) -> &[Tile] {
+        &self.tiles
+    }
+
+    /// The number of backlog units summed up in `signal_sum`.
+    pub fn backlog_length(&self) -> usize {
+        self.backlog_length
+    }
+
+    /// The number of ticks each backlog slot is written for.
+    pub fn backlog_unit(&self) -> Tick {
+        self.backlog_unit
+    }
+
+    /// Changes the window of the signal backlog, clearing all signals.
+    pub fn set_backlog(&mut self, length: usize, unit: Tick) {
+        assert!((1..=MAX_SIGNAL_BACKLOG_LENGTH).contains(&length));
+        assert!(unit.0 > 0);
+        self.backlog_length = length;
+        self.backlog_unit = unit;
+        self.clear_signals();
+    }
+
+    /// The backlog slot that [`World::pre_tick`] overwrites at `now`.
+    pub fn backlog_offset(&self, now: Tick) -> usize {
+        (now.0 / self.backlog_unit.0) as usize % self.backlog_length
+    }
+
+    pub fn probes(&self) -> &[Probe] {
+        &self.probes
+    }
+
+    /// Starts or stops sampling the tile at `xy`, returning whether it is probed now.
+    pub fn toggle_probe(&mut self, xy: (usize, usize)) -> bool {
+        if let Some(index) = self.probes.iter().position(|probe| probe.xy == xy) {
+            self.probes.remove(index);
+            return false;
+        }
+        self.probes.push(Probe {
+            xy,
+            samples: VecDeque::with_capacity(PROBE_SAMPLES),
+        });
+        true
+    }
+
+    /// The offsets of the tiles to run [`World::flagged_tick`] on in the next tick.
+    pub fn flagged_tiles(&self) -> &[usize] {
+        &self.flagged_tiles
+    }
+
+    pub fn tick(&mut self, now: Tick) {
+        self.tick_with(now, Self::pre_tick);
+    }
+
+    /// Like [`World::tick`], but rolling the backlogs with `pre_tick`.
+    fn tick_with(&mut self, now: Tick, pre_tick: fn(&mut Self, Tick)) {
+        pre_tick(self, now);
+
+        // A tile flagged by several neighbors still propagates only once,
+        // otherwise the flags would multiply every tick in connected regions.
+        self.flagged_tiles.sort_unstable();
+        self.flagged_tiles.dedup();
+
+        // the buffer of this tick's flags collects the flags of the tick after next
+        let mut flagged_tiles = mem::take(&mut self.flagged_tiles);
+        for &flagged in &flagged_tiles {
+            self.flagged_tick(flagged, now);
+        }
+        flagged_tiles.clear();
+        self.flagged_tiles = mem::replace(&mut self.next_flagged_tiles, flagged_tiles);
+
+        // with random ticks disabled, the existing signal still propagates above
+        if self.random_ticks_enabled && self.deterministic {
+            for offset in 0..self.tiles.len() {
+                self.random_tick(offset);
+            }
+        } else if self.random_ticks_enabled {
+            let results = rand::seq::index::sample(
+                &mut self.rng,
+                self.tiles.len(),
+                self.tiles.len() * self.random_tick_percentage / 100,
+            );
+            for result in results {
+                self.random_tick(result);
+            }
+        }
+
+        for probe in &mut self.probes {
+            if probe.samples.len() == PROBE_SAMPLES {
+                probe.samples.pop_front();
+            }
+            let offset = self.dim.xy_offset(probe.xy.0, probe.xy.1);
+            probe.samples.push_back(self.tiles[offset].signal_sum());
+        }
+    }
+
+    /// Runs `n` ticks starting at `start`, returning the tick to run next.
+    pub fn tick_n(&mut self, start: Tick, n: u32) -> Tick {
+        let mut now = start;
+        for _ in 0..n {
+            self.tick(now);
+            now.0 += 1;
+        }
+        now
+    }
+
+    /// Stores the `next_signal` of each tile into its backlog.
+    ///
+    /// With the `rayon` feature, busy worlds scan every tile in parallel,
+    /// which beats walking the dirty tiles once enough of them are dirty,
+    /// while mostly idle worlds keep rolling only their dirty tiles.
+    pub fn pre_tick(&mut self, now: Tick) {
+        #[cfg(feature = "rayon")]
+        if self.dirty_tiles.len() * PARALLEL_ROLL_SHARE >= self.tiles.len() {
+            self.roll_every_backlog(now);
+            return;
+        }
+        self.roll_dirty_backlogs(now);
+    }
+
+    /// Rolls the backlogs of the tiles in [`World::dirty_tiles`] only.
+    ///
+    /// Tiles are only dirty until their whole backlog caught up with next_signal,
+    /// after which rolling the backlog would not change them anymore.
+    /// Hence every change to what rolling depends on, i.e. the signal and type of a tile,
+    /// has to mark the tile dirty again.
+    fn roll_dirty_backlogs(&mut self, now: Tick) {
+        let current_signal_offset = self.backlog_offset(now);
+        let (decay, sink_drain, ceiling) = (self.decay, self.sink_drain, self.signal_ceiling);
+
+        let mut dirty_tiles = mem::take(&mut self.spare_dirty_tiles);
+        mem::swap(&mut dirty_tiles, &mut self.dirty_tiles);
+        for &offset in &dirty_tiles {
+            let tile = &mut self.tiles[offset];
+            if tile.roll_backlog(current_signal_offset, decay, sink_drain, ceiling) {
+                tile.dirty = false;
+            } else {
+                self.dirty_tiles.push(offset);
+            }
+        }
+        dirty_tiles.clear();
+        self.spare_dirty_tiles = dirty_tiles;
+    }
+
+    /// Rolls the backlog of every tile, in parallel with the `rayon` feature.
+    /// This yields the same state as [`World::roll_dirty_backlogs`],
+    /// since rolling a clean tile is a no-op.
+    #[cfg(any(test, feature = "rayon"))]
+    fn roll_every_backlog(&mut self, now: Tick) {
+        let current_signal_offset = self.backlog_offset(now);
+        let (decay, sink_drain, ceiling) = (self.decay, self.sink_drain, self.signal_ceiling);
+        let roll = |(offset, tile): (usize, &mut Tile)| {
+            tile.dirty = !tile.roll_backlog(current_signal_offset, decay, sink_drain, ceiling);
+            tile.dirty.then_some(offset)
+        };
+
+        let mut dirty_tiles = mem::take(&mut self.spare_dirty_tiles);
+        #[cfg(feature = "rayon")]
+        {
+            use rayon::prelude::*;
+            dirty_tiles.par_extend(self.tiles.par_iter_mut().enumerate().filter_map(roll));
+        }
+        #[cfg(not(feature = "rayon"))]
+        {
+            dirty_tiles.extend(self.tiles.iter_mut().enumerate().filter_map(roll));
+        }
+        mem::swap(&mut dirty_tiles, &mut self.dirty_tiles);
+        dirty_tiles.clear();
+        self.spare_dirty_tiles = dirty_tiles;
+    }
+
+    fn mark_dirty(&mut self, offset: usize) {
+        let tile = &mut self.tiles[offset];
+        if !tile.dirty {
+            tile.dirty = true;
+            self.dirty_tiles.push(offset);
+        }
+    }
+
+    /// Propagates the signal of an emitter to its accepting neighbors,
+    /// flagging each of them for the next tick with a probability,
+    /// and records `now` as the [`Tile::last_active_tick`] of the tile.
+    ///
+    /// Each neighbor receives its share of `signal_sum` rounded down,
+    /// so up to one unit of signal per neighbor is lost in every propagation.
+    /// This rounding loss is why [`World::total_signal`] is only conserved
+    /// approximately even without absorbers.
+    ///
+    /// Sides beyond the edges of a bounded world are not neighbors at all,
+    /// so they do not dilute the shares of the remaining ones:
+    /// in a 3x3 world of brick, a corner tile with a `signal_sum` of 100
+    /// gives 50 to each of its two neighbors, an edge tile gives 33 to each of three,
+    /// and the center tile gives 25 to each of four.
+    pub fn flagged_tick(&mut self, tile_offset: usize, now: Tick) {
+        self.tiles[tile_offset].last_active_tick = Some(now);
+        let (x, y) = self.dim.offset_xy(tile_offset);
+        let Tile {
+            ty,
+            signal_sums,
+            resistance,
+            ..
+        } = self.tiles[tile_offset];
+        if ty.emits(&self.rules) {
+            let conns = self.connections(x, y);
+            if conns.is_empty() {
+                // nowhere to propagate to, so the emitter retains its signal
+                return;
+            }
+
+            let next_signals = &mut self.tiles[tile_offset].next_signals;
+            for (next_signal, signal_sum) in next_signals.iter_mut().zip(signal_sums) {
+                next_signal.0 = next_signal.0.saturating_sub(signal_sum.0);
+            }
+            self.mark_dirty(tile_offset);
+
+            // conductors discard the share they resist instead of forwarding it
+            let conducted = match ty {
+                TileType::Conductor => 100 - u32::from(resistance.min(100)),
+                _ => 100,
+            };
+
+            // channels share the connections but are redistributed independently
+            let total_weight: u32 = conns.iter().map(|&(_, _, weight)| weight).sum();
+            for (x2, y2, weight) in conns {
+                let neighbor_offset = self.dim.xy_offset(x2, y2);
+                let neighbor = &mut self.tiles[neighbor_offset];
+                if !neighbor.ty.absorbs(&self.rules) {
+                    for (next_signal, signal_sum) in
+                        neighbor.next_signals.iter_mut().zip(signal_sums)
+                    {
+                        let per_side = (u32::from(signal_sum.0) * conducted / 100 * weight
+                            / total_weight) as u16;
+                        next_signal.0 = next_signal.0.saturating_add(per_side);
+                    }
+                    self.mark_dirty(neighbor_offset);
+                }
+
+                if self.deterministic || self.rng.gen_bool(self.flag_rate) {
+                    self.next_flagged_tiles.push(neighbor_offset);
+                }
+            }
+        }
+    }
+
+    /// The neighbors the tile at `(x, y)` propagates to if it emits,
+    /// with the relative weight of each.
+    fn connections(&self, x: usize, y: usize) -> ArrayVec<(usize, usize, u32), 8> {
+        let Tile { ty, facing, .. } = self[(x, y)];
+        let mut conns = ArrayVec::new();
+
+        for (side, x2, y2) in self.neighbors(x, y) {
+            // compared by tile since a tile reached from several sides is only yielded once
+            if ty == TileType::Diode && self.neighbor(x, y, SIDES[facing]) != Some((x2, y2)) {
+                continue;
+            }
+            let (dx, dy) = self.sides()[side];
+            let neighbor = &self[(x2, y2)];
+            // water settles on absorbers instead of draining into them
+            let settles = ty == TileType::Water && dy < 0 && neighbor.ty.absorbs(&self.rules);
+            // diodes do not accept signal flowing against them
+            let backflow = neighbor.ty == TileType::Diode
+                && self.neighbor(x2, y2, SIDES[neighbor.facing]) == Some((x, y));
+            let weight = ty.side_weight((dx, dy));
+            if neighbor.ty.accepts(&self.rules) && !settles && !backflow && weight > 0 {
+                conns.push((x2, y2, weight));
+            }
+        }
+        conns
+    }
+
+    /// The tiles that signal emitted at `start` can reach within `max_hops` propagations,
+    /// each with the number of hops from `start`, without running any tick.
+    /// Absorbers and other tiles that do not emit are reached but pass nothing on,
+    /// and a `start` out of bounds, e.g. after a resize, reaches nothing.
+    pub fn reach(&self, start: (usize, usize), max_hops: usize) -> Vec<((usize, usize), usize)> {
+        if start.0 >= self.dim.width || start.1 >= self.dim.height {
+            return Vec::new();
+        }
+        let mut visited = vec![false; self.tiles.len()];
+        let mut queue = VecDeque::from([(start, 0)]);
+        visited[self.dim.xy_offset(start.0, start.1)] = true;
+
+        let mut reached = Vec::new();
+        while let Some(((x, y), hops)) = queue.pop_front() {
+            reached.push(((x, y), hops));
+            if hops == max_hops || !self[(x, y)].ty.emits(&self.rules) {
+                continue;
+            }
+            for (x2, y2, _) in self.connections(x, y) {
+                let offset = self.dim.xy_offset(x2, y2);
+                if !visited[offset] {
+                    visited[offset] = true;
+                    queue.push_back(((x2, y2), hops + 1));
+                }
+            }
+        }
+        reached
+    }
+
+    /// The offsets of the neighbors signal propagates to, depending on [`World::diagonal`].
+    fn sides(&self) -> &'static [(isize, isize)] {
+        if self.diagonal {
+            &SIDES_DIAGONAL
+        } else {
+            &SIDES
+        }
+    }
+
+    /// The in-bounds neighbors of `(x, y)` as `(side, x, y)`,
+    /// where `side` indexes [`SIDES`], or the diagonal sides after them if [`World::diagonal`] is set.
+    /// Respects [`World::wrap`] like [`World::neighbor`],
+    /// yielding each tile only for the first side reaching it,
+    /// since both sides reach the same tile in wrapped worlds 2 tiles across.
+    pub fn neighbors(&self, x: usize, y: usize) -> impl Iterator<Item = (usize, usize, usize)> {
+        let mut neighbors = ArrayVec::<_, 8>::new();
+        for (side, &delta) in self.sides().iter().enumerate() {
+            if let Some((x2, y2)) = self.neighbor(x, y, delta) {
+                if !neighbors.iter().any(|&(_, x3, y3)| (x3, y3) == (x2, y2)) {
+                    neighbors.push((side, x2, y2));
+                }
+            }
+        }
+        neighbors.into_iter()
+    }
+
+    /// The coordinates of the neighbor of `(x, y)` at offset `(dx, dy)`,
+    /// wrapping around the grid edges if [`World::wrap`] is set.
+    pub fn neighbor(&self, x: usize, y: usize, (dx, dy): (isize, isize)) -> Option<(usize, usize)> {
+        let (x2, y2) = if self.wrap {
+            let x2 = (x as isize + dx).rem_euclid(self.dim.width as isize) as usize;
+            let y2 = (y as isize + dy).rem_euclid(self.dim.height as isize) as usize;
+            (x2, y2)
+        } else {
+            (x.checked_add_signed(dx)?, y.checked_add_signed(dy)?)
+        };
+
+        // in worlds one tile wide, wrapping leads back to the tile itself
+        if x2 >= self.dim.width || y2 >= self.dim.height || (x2, y2) == (x, y) {
+            return None;
+        }
+        Some((x2, y2))
+    }
+
+    /// Resets every tile to signal-free air, keeping the size and settings.
+    /// Locked tiles only lose their signal.
+    pub fn clear(&mut self) {
+        for tile in &mut self.tiles {
+            *tile = if tile.locked {
+                tile.without_signal(self.backlog_length)
+            } else {
+                Tile::new(self.backlog_length)
+            };
+        }
+        self.next_flagged_tiles.clear();
+        self.dirty_tiles.clear();
+        self.reseed_flags();
+    }
+
+    /// Resets the signal of every tile, keeping the layout,
+    /// and flags all emitters to start propagating afresh.
+    pub fn clear_signals(&mut self) {
+        for tile in &mut self.tiles {
+            *tile = tile.without_signal(self.backlog_length);
+        }
+        self.next_flagged_tiles.clear();
+        self.dirty_tiles.clear();
+        self.reseed_flags();
+    }
+
+    /// Flags every emitter for the next tick, replacing the current flags.
+    ///
+    /// Emitters only get flagged by [`World::set_type`] or by flagged neighbors,
+    /// so tiles written directly, e.g. by [`World::load`], stay inert until this is called.
+    pub fn reseed_flags(&mut self) {
+        self.flagged_tiles = (0..self.tiles.len())
+            .filter(|&offset| self.tiles[offset].ty.emits(&self.rules))
+            .collect();
+    }
+
+    /// Reallocates the grid to `width` by `height`,
+    /// keeping the tiles at the same coordinates and filling new ones with air.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        let dim = Dim { width, height };
+        let mut tiles: Vec<_> =
+            iter::repeat_n(Tile::new(self.backlog_length), width * height).collect();
+        let remap = |old: &Dim, offset: usize| {
+            let (x, y) = old.offset_xy(offset);
+            (x < width && y < height).then(|| dim.xy_offset(x, y))
+        };
+
+        for (offset, tile) in mem::take(&mut self.tiles).into_iter().enumerate() {
+            if let Some(new_offset) = remap(&self.dim, offset) {
+                tiles[new_offset] = tile;
+            }
+        }
+        for offsets in [
+            &mut self.flagged_tiles,
+            &mut self.next_flagged_tiles,
+            &mut self.dirty_tiles,
+        ] {
+            *offsets = offsets
+                .iter()
+                .filter_map(|&offset| remap(&self.dim, offset))
+                .collect();
+        }
+
+        self.probes
+            .retain(|probe| probe.xy.0 < width && probe.xy.1 < height);
+
+        self.dim = dim;
+        self.tiles = tiles;
+    }
+
+    /// Changes the type of a tile, flagging it if it becomes an emitter
+    /// so that it starts propagating in the next tick.
+    /// Setting a tile to air erases it with [`World::erase`] instead.
+    pub fn set_type(&mut self, (x, y): (usize, usize), ty: TileType) {
+        if ty == TileType::Air {
+            self.erase((x, y));
+            return;
+        }
+        let offset = self.dim.xy_offset(x, y);
+        self.tiles[offset].ty = ty;
+        // a settled tile must roll again under its new type, e.g. to start draining
+        self.mark_dirty(offset);
+        if ty.emits(&self.rules) {
+            self.flagged_tiles.push(offset);
+        }
+    }
+
+    /// Adds `amount` to the `next_signal` of the first channel of a tile of any type
+    /// and flags it, so that the pulse propagates in the next tick if the tile emits.
+    pub fn burst(&mut self, (x, y): (usize, usize), amount: Signal) {
+        let offset = self.dim.xy_offset(x, y);
+        let next_signal = &mut self.tiles[offset].next_signals[0].0;
+        *next_signal = next_signal.saturating_add(amount.0);
+        self.mark_dirty(offset);
+        self.flagged_tiles.push(offset);
+    }
+
+    /// Resets a tile to signal-free air and unflags it.
+    pub fn erase(&mut self, (x, y): (usize, usize)) {
+        let offset = self.dim.xy_offset(x, y);
+        let tile = &mut self.tiles[offset];
+        // the tile may still be queued in dirty_tiles
+        *tile = Tile {
+            dirty: tile.dirty,
+            ..Tile::new(self.backlog_length)
+        };
+        self.flagged_tiles.retain(|&flagged| flagged != offset);
+        self.next_flagged_tiles.retain(|&flagged| flagged != offset);
+    }
+
+    /// The 4-connected region of tiles sharing the type of the tile at `start`,
+    /// found breadth-first without wrapping around the grid edges.
+    pub fn region(&self, start: (usize, usize)) -> Vec<(usize, usize)> {
+        let ty = self[start].ty;
+        let mut visited = vec![false; self.tiles.len()];
+        let mut queue = VecDeque::from([start]);
+        visited[self.dim.xy_offset(start.0, start.1)] = true;
+
+        let mut region = Vec::new();
+        while let Some((x, y)) = queue.pop_front() {
+            region.push((x, y));
+
+            for (dx, dy) in SIDES {
+                let (Some(x2), Some(y2)) = (x.checked_add_signed(dx), y.checked_add_signed(dy))
+                else {
+                    continue;
+                };
+                if x2 >= self.dim.width || y2 >= self.dim.height {
+                    continue;
+                }
+                let offset = self.dim.xy_offset(x2, y2);
+                if !visited[offset] && self.tiles[offset].ty == ty {
+                    visited[offset] = true;
+                    queue.push_back((x2, y2));
+                }
+            }
+        }
+        region
+    }
+
+    /// Injects the weight of the tile as new signal into its emitted channel,
+    /// consuming one unit of [`Tile::fuel`] if it is limited.
+    pub fn random_tick(&mut self, tile_offset: usize) {
+        let tile = &mut self.tiles[tile_offset];
+        let weight = tile.weight(&self.rules);
+        if weight.0 > 0 {
+            let next_signal = &mut tile.next_signals[tile.emitted_channel()].0;
+            *next_signal = next_signal.saturating_add(weight.0);
+
+            if let Some(fuel) = &mut tile.fuel {
+                *fuel = fuel.saturating_sub(1);
+                if *fuel == 0 {
+                    // the signal already held and just injected stays in the air left behind
+                    tile.ty = TileType::Air;
+                    tile.fuel = None;
+                }
+            }
+            self.mark_dirty(tile_offset);
+        }
+    }
+
+    /// The sum of `signal_sum` over all tiles.
+    pub fn total_signal(&self) -> u64 {
+        self.tiles
+            .iter()
+            .map(|tile| u64::from(tile.signal_sum().0))
+            .sum()
+    }
+
+    pub fn max_signal_sum(&self) -> Signal {
+        self.tiles
+            .iter()
+            .map(|tile| tile.signal_sum())
+            .max_by_key(|signal| signal.0)
+            .unwrap()
+    }
+
+    /// The maximum [`Tile::signal_sums`] of each channel.
+    pub fn max_signal_sums(&self) -> [Signal; CHANNELS] {
+        let mut max = [Signal(0); CHANNELS];
+        for tile in &self.tiles {
+            for (max, signal) in max.iter_mut().zip(tile.signal_sums) {
+                max.0 = max.0.max(signal.0);
+            }
+        }
+        max
+    }
+
+    pub fn stats(&self) -> Stats {
+        let mut counts = [0; TileType::ALL.len()];
+        for tile in &self.tiles {
+            counts[tile.ty as usize] += 1;
+        }
+
+        Stats {
+            counts,
+            max_signal_sum: self.max_signal_sum(),
+            mean_signal: self.total_signal() as f64 / self.tiles.len() as f64,
+            flagged: self.flagged_tiles.len(),
+        }
+    }
+}
+
+impl ops::Index<(usize, usize)> for World {
+    type Output = Tile;
+
+    fn index(&self, (x, y): (usize, usize)) -> &Tile {
+        let offset = self.dim.xy_offset(x, y);
+        &self.tiles[offset]
+    }
+}
+
+impl ops::IndexMut<(usize, usize)> for World {
+    fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut Tile {
+        let offset = self.dim.xy_offset(x, y);
+        &mut self.tiles[offset]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `width` by `height` world of `ty`, flagged like painted by hand.
+    fn filled(width: usize, height: usize, ty: TileType, seed: u64) -> World {
+        let mut world = World::new(width, height, seed);
+        for x in 0..width {
+            for y in 0..height {
+                world.set_type((x, y), ty);
+            }
+        }
+        world
+    }
+
+    /// Gives the tile at `xy` a `signal_sum` of `amount` in channel 0 and flags it,
+    /// without running any propagation.
+    fn charge(world: &mut World, xy: (usize, usize), amount: u16) {
+        world.burst(xy, Signal(amount));
+        world.pre_tick(Tick(0));
+    }
+
+    /// Paints, erases or bursts a random tile, or leaves the world as is.
+    fn edit_randomly(world: &mut World, rng: &mut impl Rng) {
+        let xy = (
+            rng.gen_range(0..world.dim().width),
+            rng.gen_range(0..world.dim().height),
+        );
+        match rng.gen_range(0..4) {
+            0 => world.set_type(xy, TileType::ALL[rng.gen_range(0..TileType::ALL.len())]),
+            1 => world.erase(xy),
+            2 => world.burst(xy, Signal(rng.gen_range(1..1000))),
+            _ => {}
+        }
+    }
+
+    /// Ticks two copies of each of several seeded worlds through the same random edits,
+    /// rolling the backlogs of one copy with `a` and of the other with `b`,
+    /// and asserts that the copies stay identical.
+    fn assert_rolling_agrees(a: fn(&mut World, Tick), b: fn(&mut World, Tick)) {
+        for seed in 0..50 {
+            let mut rng = ChaCha12Rng::seed_from_u64(seed);
+            let mut worlds = [(); 2].map(|()| {
+                let mut world = filled(6, 5, TileType::Brick, seed);
+                world.set_type((0, 0), TileType::Sink);
+                world.decay = if seed % 2 == 0 { 1.0 } else { 0.9 };
+                world
+            });
+            for now in 0..100 {
+                let edit_seed: u64 = rng.gen();
+                for (world, pre_tick) in worlds.iter_mut().zip([a, b]) {
+                    edit_randomly(world, &mut ChaCha12Rng::seed_from_u64(edit_seed));
+                    world.tick_with(Tick(now), pre_tick);
+                }
+                assert_eq!(
+                    state(&worlds[0]),
+                    state(&worlds[1]),
+                    "seed {seed}, tick {now}"
+                );
+            }
+        }
+    }
+
+    /// Asserts the `next_signal` of every tile after the tile at `xy` emits a `signal_sum` of 100,
+    /// where `expected` lists the tiles that receive signal.
+    fn assert_spread(mut world: World, xy: (usize, usize), expected: &[((usize, usize), u16)]) {
+        charge(&mut world, xy, 100);
+        world.flagged_tick(world.dim().xy_offset(xy.0, xy.1), Tick(0));
+        for y in 0..world.dim().height {
+            for x in 0..world.dim().width {
+                let share = expected
+                    .iter()
+                    .find(|&&(neighbor, _)| neighbor == (x, y))
+                    .map_or(0, |&(_, share)| share);
+                assert_eq!(
+                    world[(x, y)].next_signal().0,
+                    share,
+                    "({x}, {y}) from {xy:?}"
+                );
+            }
+        }
+    }
+
+    /// The `signal_sums` and `next_signals` of every tile.
+    fn state(world: &World) -> Vec<([u16; CHANNELS], [u16; CHANNELS])> {
+        world
+            .tiles()
+            .iter()
+            .map(|tile| {
+                (
+                    tile.signal_sums().map(|signal| signal.0),
+                    tile.next_signals().map(|signal| signal.0),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn dim_offsets_round_trip() {
+        for (width, height) in [(1, 1), (1, 5), (5, 1), (3, 4), (16, 9)] {
+            let dim = Dim { width, height };
+            for y in 0..height {
+                for x in 0..width {
+                    assert_eq!(dim.offset_xy(dim.xy_offset(x, y)), (x, y));
+                }
+            }
+            for offset in 0..width * height {
+                let (x, y) = dim.offset_xy(offset);
+                assert_eq!(dim.xy_offset(x, y), offset);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn dim_rejects_out_of_range_x() {
+        Dim {
+            width: 3,
+            height: 4,
+        }
+        .xy_offset(3, 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn dim_rejects_out_of_range_y() {
+        Dim {
+            width: 3,
+            height: 4,
+        }
+        .xy_offset(0, 4);
+    }
+
+    #[test]
+    #[should_panic]
+    fn dim_rejects_out_of_range_offsets() {
+        Dim {
+            width: 3,
+            height: 4,
+        }
+        .offset_xy(12);
+    }
+
+    #[test]
+    fn new_world_has_a_tile_per_cell() {
+        for (width, height) in [(1, 1), (1, 7), (5, 1), (3, 3), (80, 40), (81, 41)] {
+            let world = World::new(width, height, 0);
+            assert_eq!(world.tiles().len(), width * height);
+        }
+    }
+
+    #[test]
+    fn same_seed_ticks_identically() {
+        let [mut a, mut b] = [42, 42].map(|seed| {
+            let mut world = filled(8, 4, TileType::Brick, seed);
+            for x in 0..8 {
+                world.set_type((x, 0), TileType::Bedrock);
+            }
+            world
+        });
+        for now in 0..50 {
+            a.tick(Tick(now));
+            b.tick(Tick(now));
+        }
+        assert_eq!(state(&a), state(&b));
+        assert!(a.total_signal() > 0);
+    }
+
+    #[test]
+    fn isolated_emitter_retains_its_signal() {
+        // in the center and in a corner, where sides are partly out of bounds
+        for xy in [(1, 1), (0, 0)] {
+            let mut world = World::new(3, 3, 0);
+            world.set_type(xy, TileType::Brick);
+            charge(&mut world, xy, 100);
+
+            world.flagged_tick(world.dim().xy_offset(xy.0, xy.1), Tick(0));
+            assert_eq!(world[xy].next_signal().0, 100);
+            assert_eq!(world[xy].signal_sum().0, 100);
+        }
+    }
+
+    #[test]
+    fn diodes_only_pass_signal_forwards() {
+        // brick, a diode facing +x, brick
+        let diode_world = || {
+            let mut world = filled(3, 1, TileType::Brick, 0);
+            world.set_type((1, 0), TileType::Diode);
+            world[(1, 0)].facing = 0;
+            world
+        };
+
+        let mut world = diode_world();
+        charge(&mut world, (0, 0), 100);
+        world.flagged_tick(0, Tick(0));
+        assert_eq!(world[(1, 0)].next_signal().0, 100);
+
+        let mut world = diode_world();
+        charge(&mut world, (2, 0), 100);
+        world.flagged_tick(2, Tick(0));
+        assert_eq!(world[(1, 0)].next_signal().0, 0);
+        assert_eq!(world[(2, 0)].next_signal().0, 100);
+
+        let mut world = diode_world();
+        charge(&mut world, (1, 0), 100);
+        world.flagged_tick(1, Tick(0));
+        assert_eq!(world[(0, 0)].next_signal().0, 0);
+        assert_eq!(world[(2, 0)].next_signal().0, 100);
+    }
+
+    #[test]
+    fn wrapping_tiny_worlds_splits_once_per_neighbor() {
+        let wrapped = |width, height| {
+            let mut world = filled(width, height, TileType::Brick, 0);
+            world.wrap = true;
+            world
+        };
+        // left and right are the same tile, as are up and down in 2 tall worlds
+        assert_eq!(wrapped(2, 3).neighbors(0, 1).count(), 3);
+        assert_eq!(wrapped(2, 2).neighbors(0, 0).count(), 2);
+        assert_spread(
+            wrapped(2, 3),
+            (0, 1),
+            &[((1, 1), 33), ((0, 0), 33), ((0, 2), 33)],
+        );
+        assert_spread(wrapped(2, 2), (1, 1), &[((0, 1), 50), ((1, 0), 50)]);
+
+        // a diode still passes signal on to the tile it faces from either side
+        for facing in [0, 1] {
+            let mut world = wrapped(2, 1);
+            world.set_type((0, 0), TileType::Diode);
+            world[(0, 0)].facing = facing;
+            assert_spread(world, (0, 0), &[((1, 0), 100)]);
+        }
+    }
+
+    #[test]
+    fn wrapping_reaches_the_opposite_edge_in_one_tick() {
+        for wrap in [false, true] {
+            let mut world = filled(5, 1, TileType::Brick, 0);
+            world.wrap = wrap;
+            charge(&mut world, (0, 0), 100);
+            world.flagged_tick(0, Tick(0));
+
+            let expected = if wrap { 50 } else { 0 };
+            assert_eq!(world[(4, 0)].next_signal().0, expected);
+        }
+    }
+
+    #[test]
+    fn ticks_conserve_signal_up_to_rounding() {
+        let mut world = filled(3, 3, TileType::Brick, 0);
+        world.deterministic = true;
+        world.set_backlog(1, Tick(1));
+        // every tile is random ticked in deterministic worlds
+        let emitted: u64 = world
+            .tiles()
+            .iter()
+            .map(|tile| u64::from(tile.weight(&world.rules).0))
+            .sum();
+
+        let mut now = world.tick_n(Tick(0), 1);
+        for _ in 0..20 {
+            let before = world.total_signal();
+            now = world.tick_n(now, 1);
+            // each of the 9 emitters loses less than 1 per connection to per_side rounding
+            let lost = (before + emitted)
+                .checked_sub(world.total_signal())
+                .expect("signal is created");
+            assert!(lost < 9 * 4, "{lost} lost at {now:?}");
+        }
+    }
+
+    #[test]
+    fn sinks_drain_signal_monotonically() {
+        let mut world = filled(3, 1, TileType::Sink, 0);
+        world.random_ticks_enabled = false;
+        world.set_backlog(1, Tick(1));
+        world.burst((1, 0), Signal(1000));
+
+        let mut now = world.tick_n(Tick(0), 1);
+        let mut total = world.total_signal();
+        assert!(total > 0);
+        while total > 0 {
+            assert!(now.0 < 1000, "still {total} at {now:?}");
+            now = world.tick_n(now, 1);
+            assert!(
+                world.total_signal() < total,
+                "{total} did not drain at {now:?}"
+            );
+            total = world.total_signal();
+        }
+    }
+
+    #[test]
+    fn resizing_keeps_tiles_at_their_coordinates() {
+        let mut world = World::new(4, 3, 0);
+        world.set_type((2, 1), TileType::Brick);
+        world.set_type((3, 2), TileType::Water);
+
+        // the water is cut off by shrinking and not restored by growing again
+        let mut water = true;
+        for (width, height) in [(7, 5), (3, 2), (5, 4)] {
+            world.resize(width, height);
+            water &= width > 3 && height > 2;
+            assert_eq!(world.tiles().len(), width * height);
+            for y in 0..height {
+                for x in 0..width {
+                    let expected = match (x, y) {
+                        (2, 1) => TileType::Brick,
+                        (3, 2) if water => TileType::Water,
+                        _ => TileType::Air,
+                    };
+                    assert_eq!(world[(x, y)].ty, expected, "({x}, {y}) in {width}x{height}");
+                }
+            }
+            let brick = world.dim().xy_offset(2, 1);
+            assert!(world.flagged_tiles().contains(&brick));
+            assert!(world
+                .flagged_tiles()
+                .iter()
+                .all(|&offset| offset < width * height));
+        }
+        world.tick_n(Tick(0), 10);
+    }
+
+    #[test]
+    fn erasing_resets_the_signal_of_a_tile() {
+        let mut world = filled(3, 3, TileType::Brick, 0);
+        world.tick_n(Tick(0), 20);
+        assert!(world[(1, 1)].signal_sum().0 > 0);
+
+        world.erase((1, 1));
+        let center = world.dim().xy_offset(1, 1);
+        let tile = &world[(1, 1)];
+        assert_eq!(tile.ty, TileType::Air);
+        assert_eq!(tile.signal_sum().0, 0);
+        assert_eq!(tile.next_signal().0, 0);
+        assert_eq!(tile.signals().len(), DEFAULT_SIGNAL_BACKLOG_LENGTH);
+        assert!(!world.flagged_tiles().contains(&center));
+    }
+
+    #[test]
+    fn isolated_air_loses_its_signal() {
+        let mut world = World::new(1, 1, 0);
+        world.burst((0, 0), Signal(1000));
+        let mut now = world.tick_n(Tick(0), 1);
+        let peak = world[(0, 0)].signal_sum().0;
+        assert!(peak > 0);
+
+        // the sum may briefly grow when the backlog moves on to the next slot,
+        // but never back to the peak
+        let mut signal_sum = peak;
+        while signal_sum > 0 {
+            assert!(now.0 < 100, "still {signal_sum} at {now:?}");
+            now = world.tick_n(now, 1);
+            signal_sum = world[(0, 0)].signal_sum().0;
+            assert!(signal_sum < peak, "{signal_sum} at {now:?}");
+        }
+    }
+
+    #[test]
+    fn backlog_offsets_wrap_at_window_boundaries() {
+        let mut world = World::new(1, 1, 0);
+        for (length, unit, offsets) in [
+            (
+                4,
+                8,
+                [(0, 0), (7, 0), (8, 1), (31, 3), (32, 0), (39, 0), (40, 1)],
+            ),
+            (
+                3,
+                5,
+                [(0, 0), (4, 0), (5, 1), (14, 2), (15, 0), (29, 2), (30, 0)],
+            ),
+            (
+                1,
+                1,
+                [
+                    (0, 0),
+                    (1, 0),
+                    (2, 0),
+                    (7, 0),
+                    (8, 0),
+                    (9, 0),
+                    (u32::MAX, 0),
+                ],
+            ),
+        ] {
+            world.set_backlog(length, Tick(unit));
+            for (now, offset) in offsets {
+                assert_eq!(
+                    world.backlog_offset(Tick(now)),
+                    offset,
+                    "{now} with {length} slots of {unit}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn emitters_split_signal_evenly_among_their_neighbors() {
+        let square = || filled(3, 3, TileType::Brick, 0);
+        // per_side is 100 / 2 at corners, 100 / 3 at edges and 100 / 4 in the center
+        assert_spread(square(), (0, 0), &[((1, 0), 50), ((0, 1), 50)]);
+        assert_spread(square(), (2, 2), &[((1, 2), 50), ((2, 1), 50)]);
+        assert_spread(
+            square(),
+            (1, 0),
+            &[((0, 0), 33), ((2, 0), 33), ((1, 1), 33)],
+        );
+        assert_spread(
+            square(),
+            (0, 1),
+            &[((0, 0), 33), ((0, 2), 33), ((1, 1), 33)],
+        );
+        assert_spread(
+            square(),
+            (1, 1),
+            &[((0, 1), 25), ((2, 1), 25), ((1, 0), 25), ((1, 2), 25)],
+        );
+
+        // the ends of a line have a single neighbor
+        let column = || filled(1, 4, TileType::Brick, 0);
+        assert_spread(column(), (0, 0), &[((0, 1), 100)]);
+        assert_spread(column(), (0, 3), &[((0, 2), 100)]);
+        assert_spread(column(), (0, 1), &[((0, 0), 50), ((0, 2), 50)]);
+        let row = || filled(4, 1, TileType::Brick, 0);
+        assert_spread(row(), (3, 0), &[((2, 0), 100)]);
+        assert_spread(row(), (2, 0), &[((1, 0), 50), ((3, 0), 50)]);
+    }
+
+    #[test]
+    fn walls_leave_fewer_sides_to_split_among() {
+        let mut world = filled(3, 3, TileType::Brick, 0);
+        world.set_type((2, 1), TileType::Wall);
+        assert_spread(world, (1, 1), &[((0, 1), 33), ((1, 0), 33), ((1, 2), 33)]);
+    }
+
+    #[test]
+    fn flag_rate_bounds_reflag_all_or_no_neighbors() {
+        for (flag_rate, expected) in [(1.0, vec![(1, 0), (0, 1), (2, 1), (1, 2)]), (0.0, vec![])] {
+            for seed in 0..20 {
+                let mut world = filled(3, 3, TileType::Brick, seed);
+                world.flag_rate = flag_rate;
+                charge(&mut world, (1, 1), 100);
+                world.flagged_tick(world.dim().xy_offset(1, 1), Tick(0));
+
+                let mut flagged = world.next_flagged_tiles.clone();
+                flagged.sort_unstable();
+                let expected: Vec<_> = expected
+                    .iter()
+                    .map(|&(x, y)| world.dim().xy_offset(x, y))
+                    .collect();
+                assert_eq!(flagged, expected, "rate {flag_rate}, seed {seed}");
+            }
+        }
+    }
+
+    #[test]
+    fn signal_decreases_along_a_chain_of_conductors() {
+        let mut world = filled(6, 1, TileType::Conductor, 0);
+        world.deterministic = true;
+        world.random_ticks_enabled = false;
+        world.set_backlog(1, Tick(1));
+        world.burst((0, 0), Signal(1000));
+
+        let mut peaks = [0; 6];
+        let mut now = Tick(0);
+        for _ in 0..50 {
+            now = world.tick_n(now, 1);
+            for (x, peak) in peaks.iter_mut().enumerate() {
+                *peak = world[(x, 0)].signal_sum().0.max(*peak);
+            }
+        }
+        // the first conductor forwards 90% of its signal at the default resistance
+        assert_eq!(peaks[..2], [1000, 900]);
+        assert!(peaks.windows(2).all(|pair| pair[0] > pair[1]), "{peaks:?}");
+        assert_eq!(world.total_signal(), 0);
+    }
+
+    #[test]
+    fn neighbors_stay_in_bounds() {
+        let mut world = World::new(3, 3, 0);
+        let neighbors = |world: &World, xy: (usize, usize)| {
+            let mut neighbors: Vec<_> = world
+                .neighbors(xy.0, xy.1)
+                .map(|(side, x2, y2)| {
+                    // sides point at the neighbor unless it is wrapped around
+                    let (dx, dy) = world.sides()[side];
+                    if !world.wrap {
+                        assert_eq!(
+                            (xy.0 as isize + dx, xy.1 as isize + dy),
+                            (x2 as isize, y2 as isize)
+                        );
+                    }
+                    (x2, y2)
+                })
+                .collect();
+            neighbors.sort_unstable();
+            neighbors
+        };
+
+        assert_eq!(neighbors(&world, (0, 0)), [(0, 1), (1, 0)]);
+        assert_eq!(neighbors(&world, (2, 1)), [(1, 1), (2, 0), (2, 2)]);
+        assert_eq!(neighbors(&world, (1, 1)), [(0, 1), (1, 0), (1, 2), (2, 1)]);
+
+        world.wrap = true;
+        assert_eq!(neighbors(&world, (0, 0)), [(0, 1), (0, 2), (1, 0), (2, 0)]);
+        world.wrap = false;
+
+        world.diagonal = true;
+        assert_eq!(neighbors(&world, (0, 0)), [(0, 1), (1, 0), (1, 1)]);
+        assert_eq!(neighbors(&world, (1, 1)).len(), 8);
+        assert!(!neighbors(&world, (1, 1)).contains(&(1, 1)));
+    }
+
+    #[test]
+    fn sources_stop_emitting_when_out_of_fuel() {
+        let mut world = World::new(1, 1, 0);
+        world.set_type((0, 0), TileType::Source);
+        world[(0, 0)].fuel = Some(3);
+
+        let mut emitted = Vec::new();
+        for _ in 0..5 {
+            world.random_tick(0);
+            emitted.push(world[(0, 0)].next_signal().0);
+        }
+        let strength = DEFAULT_SOURCE_STRENGTH.0;
+        assert_eq!(emitted, [1, 2, 3, 3, 3].map(|n| n * strength));
+        assert_eq!(world[(0, 0)].ty, TileType::Air);
+        assert_eq!(world[(0, 0)].fuel, None);
+    }
+
+    #[test]
+    fn deterministic_worlds_ignore_the_seed() {
+        let worlds = [1, 2].map(|seed| {
+            let mut world = filled(8, 6, TileType::Brick, seed);
+            world.deterministic = true;
+            for x in 0..8 {
+                world.set_type((x, 0), TileType::Bedrock);
+                world.set_type((x, 4), TileType::Water);
+            }
+            world.set_type((3, 2), TileType::Source);
+            world.set_type((5, 2), TileType::Sink);
+            world.tick_n(Tick(0), 100);
+            world
+        });
+        assert!(worlds[0].total_signal() > 0);
+        assert_eq!(state(&worlds[0]), state(&worlds[1]));
+    }
+
+    #[test]
+    fn reach_from_out_of_bounds_is_empty() {
+        let mut world = filled(4, 4, TileType::Brick, 0);
+        assert_eq!(world.reach((3, 3), 1).len(), 3);
+        world.resize(2, 2);
+        assert!(world.reach((3, 3), 1).is_empty());
+        assert!(world.reach((0, 2), 1).is_empty());
+    }
+
+    #[test]
+    fn retyped_settled_tile_rolls_again() {
+        let mut world = filled(1, 1, TileType::Brick, 0);
+        world.random_ticks_enabled = false;
+        world.burst((0, 0), Signal(1000));
+        let now = world.tick_n(Tick(0), 40);
+        assert_eq!(world[(0, 0)].signal_sum().0, 4000);
+
+        world.set_type((0, 0), TileType::Sink);
+        world.tick_n(now, 40);
+        assert!(world[(0, 0)].signal_sum().0 < 4000);
+    }
+
+    #[test]
+    fn rolling_dirty_backlogs_matches_rolling_every_backlog() {
+        assert_rolling_agrees(World::roll_dirty_backlogs, World::roll_every_backlog);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_ticks_match_sequential_ticks() {
+        assert_rolling_agrees(World::pre_tick, World::roll_dirty_backlogs);
+    }
+
+    #[test]
+    fn retyping_while_ticking_keeps_backlog_sums() {
+        let mut world = filled(4, 4, TileType::Brick, 9);
+        let types = [
+            TileType::Bedrock,
+            TileType::Air,
+            TileType::Sink,
+            TileType::Brick,
+        ];
+        for now in 0..200 {
+            world.set_type((1, 1), types[now as usize % types.len()]);
+            world.set_type((2, 1), types[now as usize / 3 % types.len()]);
+            world.tick(Tick(now));
+
+            for tile in world.tiles() {
+                for channel in 0..CHANNELS {
+                    let sum: u32 = tile
+                        .signals()
+                        .iter()
+                        .map(|slot| u32::from(slot[channel].0))
+                        .sum();
+                    assert_eq!(sum, u32::from(tile.signal_sums()[channel].0));
+                }
+            }
+        }
+    }
+}